@@ -2,7 +2,9 @@ use bevy::{prelude::*, render::camera::ScalingMode};
 use bevy_pixel_map::{
     chunk::{Chunk, ChunkBundle},
     plugin::PixelPlugin,
+    position::{ChunkPos, LocalTilePos, PixelPos},
     tile::Tile,
+    TILE_SIZE,
 };
 
 pub fn main() {
@@ -20,13 +22,22 @@ pub fn setup_system(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
     camera_bundle.projection.scaling_mode = ScalingMode::WindowSize(25.6);
     commands.spawn(camera_bundle);
 
-    commands.spawn(ChunkBundle::new(IVec2::new(0, 0), Chunk::new(&mut images)));
+    commands.spawn(ChunkBundle::new(
+        ChunkPos::new(0, 0),
+        Chunk::new(&mut images),
+    ));
 }
 
 pub fn set_tons_of_tiles(mut commands: Commands, mut chunks: Query<(Entity, &mut Chunk)>) {
-    let mut tile = Tile::from_color(Color::rgba(1.0, 1.0, 1.0, 1.0));
-    tile.set_pixel(IVec2::new(1, 5), Color::rgba(0.0, 1.0, 0.0, 1.0));
+    let mut tile = Tile::from_color(Color::rgba(1.0, 1.0, 1.0, 1.0), TILE_SIZE);
+    tile.set_pixel(PixelPos::new(1, 5), Color::rgba(0.0, 1.0, 0.0, 1.0));
     for (entity, mut chunk) in &mut chunks {
-        chunk.set_tile(entity, IVec2::new(3, 2), tile.clone(), (), &mut commands);
+        chunk.set_tile(
+            entity,
+            LocalTilePos::new(3, 2),
+            tile.clone(),
+            (),
+            &mut commands,
+        );
     }
 }
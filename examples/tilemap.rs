@@ -6,6 +6,7 @@ use bevy_pixel_map::multi_tile::MultiTile;
 #[derive(Default, Resource)]
 pub struct TileResource {
     image_handles: HashMap<String, Handle<Image>>,
+    tileset: Handle<Tileset>,
 }
 
 #[derive(Default, Resource)]
@@ -52,43 +53,43 @@ pub fn setup_system(
     tile_resource
         .image_handles
         .insert("Lightslate".to_string(), image_resoure);
+    tile_resource.tileset = asset_server.load("lightslate.tileset.ron");
 }
 
 fn placement_system(
     mut commands: Commands,
-    mut tilemaps: Query<&mut Tilemap>,
+    mut tilemaps: Query<(&mut Tilemap, &GlobalTransform)>,
     input: Res<Input<MouseButton>>,
     keys: Res<Input<KeyCode>>,
     mouse_pos_resource: Res<MousePosResource>,
 
-    images: Res<Assets<Image>>,
+    (images, tilesets): (Res<Assets<Image>>, Res<Assets<Tileset>>),
     tile_resource: Res<TileResource>,
 ) {
+    let (mut tilemap, transform) = tilemaps.single_mut();
+
     // Now convert to tile coords.
-    let (tile_coord, pixel_coord) = world_unit_to_pixel(mouse_pos_resource.mouse_pos);
+    let (tile_coord, pixel_coord) =
+        tilemap.global_to_pixel(transform, mouse_pos_resource.mouse_pos);
 
     if input.pressed(MouseButton::Left) {
-        let image = images
-            .get(tile_resource.image_handles.get("Lightslate").unwrap())
-            .unwrap();
-        // Set the tile at that location
-        tilemaps.single_mut().set_tile(
-            &mut commands,
-            tile_coord,
-            Tile::from_image(image, (16..24, 16..24)),
-            (),
-        );
+        // The tileset isn't there until it's loaded
+        if let Some(tile) = tilesets
+            .get(&tile_resource.tileset)
+            .and_then(|tileset| tileset.get_by_name("slate"))
+        {
+            // Set the tile at that location
+            tilemap.set_tile(&mut commands, tile_coord, tile, ());
+        }
     }
 
     if input.pressed(MouseButton::Right) {
-        tilemaps.single_mut().delete_tile(tile_coord);
+        tilemap.delete_tile(tile_coord);
     }
 
     if keys.pressed(KeyCode::P) {
         // Set the tile's pixel to red
-        tilemaps
-            .single_mut()
-            .set_pixel(tile_coord, pixel_coord, Color::RED);
+        tilemap.set_pixel(tile_coord, pixel_coord, Color::RED);
     }
 
     if keys.pressed(KeyCode::M) {
@@ -98,6 +99,6 @@ fn placement_system(
             .unwrap();
 
         let tile = MultiTile::from_image(image);
-        tile.place(tile_coord, &mut tilemaps.single_mut(), &mut commands);
+        tile.place(tile_coord, &mut tilemap, &mut commands);
     }
 }
@@ -0,0 +1,397 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    animation::AnimatedTile,
+    autotile::{AutoTileSetId, AutoTileSets},
+    chunk::Chunk,
+    image_tile::TileImageSource,
+    material::{MaterialId, MaterialRegistry},
+    overlap::{PixelOverlap, PixelShape, PixelSweepHit},
+    position::{PixelPos, TilePos, WorldPixelPos},
+    raycast::{PixelRay, PixelRayHit},
+    tile::Tile,
+    tile_kind::{TileKind, TileRegistry},
+    tilemap::{SetTileError, Tilemap},
+};
+
+/// Read and write access to every tilemap, without needing separate chunk and tile queries.
+///
+/// Writes are queued on the tilemap and applied in `PostUpdate`, like [`Tilemap::set_tile`].
+/// Methods return `None` or do nothing when `tilemap` isn't a tilemap entity.
+#[derive(SystemParam)]
+pub struct TilemapAccess<'w, 's> {
+    commands: Commands<'w, 's>,
+    tilemaps: Query<'w, 's, &'static mut Tilemap>,
+    chunks: Query<'w, 's, &'static Chunk>,
+    tiles: Query<'w, 's, &'static Tile>,
+}
+
+impl<'w, 's> TilemapAccess<'w, 's> {
+    pub fn commands(&mut self) -> &mut Commands<'w, 's> {
+        &mut self.commands
+    }
+
+    pub fn tilemap(&self, tilemap: Entity) -> Option<&Tilemap> {
+        self.tilemaps.get(tilemap).ok()
+    }
+
+    pub fn tile(&self, tile: Entity) -> Option<&Tile> {
+        self.tiles.get(tile).ok()
+    }
+
+    pub fn get_tile(&self, tilemap: Entity, loc: TilePos) -> Option<Entity> {
+        self.tilemap(tilemap)?.get_tile(loc, &self.chunks)
+    }
+
+    pub fn get_pixel(&self, tilemap: Entity, loc: TilePos, pixel: PixelPos) -> Option<Color> {
+        self.tilemap(tilemap)?
+            .get_pixel(loc, pixel, &self.chunks, &self.tiles)
+    }
+
+    pub fn set_tile(
+        &mut self,
+        tilemap: Entity,
+        loc: TilePos,
+        tile: Tile,
+        additional_components: impl Bundle,
+    ) -> Option<Entity> {
+        let mut tilemap = self.tilemaps.get_mut(tilemap).ok()?;
+        tilemap.set_tile(&mut self.commands, loc, tile, additional_components)
+    }
+
+    pub fn set_image_tile(
+        &mut self,
+        tilemap: Entity,
+        loc: TilePos,
+        source: TileImageSource,
+        images: &Assets<Image>,
+        additional_components: impl Bundle,
+    ) -> Option<Entity> {
+        let mut tilemap = self.tilemaps.get_mut(tilemap).ok()?;
+        tilemap.set_image_tile(
+            &mut self.commands,
+            loc,
+            source,
+            images,
+            additional_components,
+        )
+    }
+
+    pub fn set_animated_tile(
+        &mut self,
+        tilemap: Entity,
+        loc: TilePos,
+        animation: AnimatedTile,
+        additional_components: impl Bundle,
+    ) -> Option<Entity> {
+        let mut tilemap = self.tilemaps.get_mut(tilemap).ok()?;
+        tilemap.set_animated_tile(&mut self.commands, loc, animation, additional_components)
+    }
+
+    pub fn set_auto_tile(
+        &mut self,
+        tilemap: Entity,
+        loc: TilePos,
+        set: AutoTileSetId,
+        sets: &AutoTileSets,
+        additional_components: impl Bundle,
+    ) -> Option<Entity> {
+        let mut tilemap = self.tilemaps.get_mut(tilemap).ok()?;
+        tilemap.set_auto_tile(&mut self.commands, loc, set, sets, additional_components)
+    }
+
+    pub fn set_tile_kind(
+        &mut self,
+        tilemap: Entity,
+        loc: TilePos,
+        kind: TileKind,
+        registry: &TileRegistry,
+        additional_components: impl Bundle,
+    ) -> Option<Entity> {
+        let mut tilemap = self.tilemaps.get_mut(tilemap).ok()?;
+        tilemap.set_tile_kind(
+            &mut self.commands,
+            loc,
+            kind,
+            registry,
+            additional_components,
+        )
+    }
+
+    pub fn try_set_tile(
+        &mut self,
+        tilemap: Entity,
+        loc: TilePos,
+        tile: Tile,
+        additional_components: impl Bundle,
+    ) -> Result<Entity, SetTileError> {
+        let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) else {
+            return Err(SetTileError::NotATilemap(tilemap));
+        };
+        tilemap.try_set_tile(
+            &mut self.commands,
+            &self.chunks,
+            loc,
+            tile,
+            additional_components,
+        )
+    }
+
+    pub fn set_pixel(&mut self, tilemap: Entity, loc: TilePos, pixel: PixelPos, color: Color) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.set_pixel(loc, pixel, color)
+        }
+    }
+
+    pub fn get_world_pixel(&self, tilemap: Entity, world_pixel: WorldPixelPos) -> Option<Color> {
+        self.tilemap(tilemap)?
+            .get_world_pixel(world_pixel, &self.chunks, &self.tiles)
+    }
+
+    pub fn get_world_material(
+        &self,
+        tilemap: Entity,
+        world_pixel: WorldPixelPos,
+    ) -> Option<MaterialId> {
+        self.tilemap(tilemap)?
+            .get_world_material(world_pixel, &self.chunks, &self.tiles)
+    }
+
+    pub fn raycast(
+        &self,
+        tilemap: Entity,
+        ray: PixelRay,
+        materials: &MaterialRegistry,
+    ) -> Option<PixelRayHit> {
+        self.tilemap(tilemap)?
+            .raycast(ray, materials, &self.chunks, &self.tiles)
+    }
+
+    pub fn overlap(
+        &self,
+        tilemap: Entity,
+        shape: &PixelShape,
+        alpha_threshold: f32,
+        materials: &MaterialRegistry,
+    ) -> Vec<PixelOverlap> {
+        self.tilemap(tilemap)
+            .map(|tilemap| {
+                tilemap.overlap(shape, alpha_threshold, materials, &self.chunks, &self.tiles)
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn overlaps(
+        &self,
+        tilemap: Entity,
+        shape: &PixelShape,
+        alpha_threshold: f32,
+        materials: &MaterialRegistry,
+    ) -> bool {
+        self.tilemap(tilemap).is_some_and(|tilemap| {
+            tilemap.overlaps(shape, alpha_threshold, materials, &self.chunks, &self.tiles)
+        })
+    }
+
+    pub fn sweep(
+        &self,
+        tilemap: Entity,
+        shape: &PixelShape,
+        motion: IVec2,
+        alpha_threshold: f32,
+        materials: &MaterialRegistry,
+    ) -> Option<PixelSweepHit> {
+        self.tilemap(tilemap)?.sweep(
+            shape,
+            motion,
+            alpha_threshold,
+            materials,
+            &self.chunks,
+            &self.tiles,
+        )
+    }
+
+    pub fn set_world_pixel(&mut self, tilemap: Entity, world_pixel: WorldPixelPos, color: Color) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.set_world_pixel(world_pixel, color)
+        }
+    }
+
+    pub fn set_world_pixel_or_create(
+        &mut self,
+        tilemap: Entity,
+        world_pixel: WorldPixelPos,
+        color: Color,
+    ) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.set_world_pixel_or_create(world_pixel, color)
+        }
+    }
+
+    pub fn clear_world_pixel(&mut self, tilemap: Entity, world_pixel: WorldPixelPos) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.clear_world_pixel(world_pixel)
+        }
+    }
+
+    pub fn carve(&mut self, tilemap: Entity, shape: &PixelShape) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.carve(shape)
+        }
+    }
+
+    pub fn carve_circle(&mut self, tilemap: Entity, center: WorldPixelPos, radius: u32) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.carve_circle(center, radius)
+        }
+    }
+
+    pub fn carve_polygon(&mut self, tilemap: Entity, points: &[Vec2]) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.carve_polygon(points)
+        }
+    }
+
+    pub fn fill(&mut self, tilemap: Entity, shape: &PixelShape, color: Color) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.fill(shape, color)
+        }
+    }
+
+    pub fn fill_circle(
+        &mut self,
+        tilemap: Entity,
+        center: WorldPixelPos,
+        radius: u32,
+        color: Color,
+    ) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.fill_circle(center, radius, color)
+        }
+    }
+
+    pub fn set_world_material(
+        &mut self,
+        tilemap: Entity,
+        world_pixel: WorldPixelPos,
+        material: MaterialId,
+        registry: &MaterialRegistry,
+    ) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.set_world_material(world_pixel, material, registry)
+        }
+    }
+
+    pub fn fill_material(
+        &mut self,
+        tilemap: Entity,
+        shape: &PixelShape,
+        material: MaterialId,
+        registry: &MaterialRegistry,
+    ) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.fill_material(shape, material, registry)
+        }
+    }
+
+    pub fn delete_tile(&mut self, tilemap: Entity, loc: TilePos) {
+        if let Ok(mut tilemap) = self.tilemaps.get_mut(tilemap) {
+            tilemap.delete_tile(loc)
+        }
+    }
+}
+
+/// Read only access to every tilemap, only needing `&Tilemap`
+#[derive(SystemParam)]
+pub struct TilemapRead<'w, 's> {
+    tilemaps: Query<'w, 's, &'static Tilemap>,
+    chunks: Query<'w, 's, &'static Chunk>,
+    tiles: Query<'w, 's, &'static Tile>,
+}
+
+impl<'w, 's> TilemapRead<'w, 's> {
+    pub fn tilemap(&self, tilemap: Entity) -> Option<&Tilemap> {
+        self.tilemaps.get(tilemap).ok()
+    }
+
+    pub fn tile(&self, tile: Entity) -> Option<&Tile> {
+        self.tiles.get(tile).ok()
+    }
+
+    pub fn get_tile(&self, tilemap: Entity, loc: TilePos) -> Option<Entity> {
+        self.tilemap(tilemap)?.get_tile(loc, &self.chunks)
+    }
+
+    pub fn get_pixel(&self, tilemap: Entity, loc: TilePos, pixel: PixelPos) -> Option<Color> {
+        self.tilemap(tilemap)?
+            .get_pixel(loc, pixel, &self.chunks, &self.tiles)
+    }
+
+    pub fn get_world_pixel(&self, tilemap: Entity, world_pixel: WorldPixelPos) -> Option<Color> {
+        self.tilemap(tilemap)?
+            .get_world_pixel(world_pixel, &self.chunks, &self.tiles)
+    }
+
+    pub fn get_world_material(
+        &self,
+        tilemap: Entity,
+        world_pixel: WorldPixelPos,
+    ) -> Option<MaterialId> {
+        self.tilemap(tilemap)?
+            .get_world_material(world_pixel, &self.chunks, &self.tiles)
+    }
+
+    pub fn raycast(
+        &self,
+        tilemap: Entity,
+        ray: PixelRay,
+        materials: &MaterialRegistry,
+    ) -> Option<PixelRayHit> {
+        self.tilemap(tilemap)?
+            .raycast(ray, materials, &self.chunks, &self.tiles)
+    }
+
+    pub fn overlap(
+        &self,
+        tilemap: Entity,
+        shape: &PixelShape,
+        alpha_threshold: f32,
+        materials: &MaterialRegistry,
+    ) -> Vec<PixelOverlap> {
+        self.tilemap(tilemap)
+            .map(|tilemap| {
+                tilemap.overlap(shape, alpha_threshold, materials, &self.chunks, &self.tiles)
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn overlaps(
+        &self,
+        tilemap: Entity,
+        shape: &PixelShape,
+        alpha_threshold: f32,
+        materials: &MaterialRegistry,
+    ) -> bool {
+        self.tilemap(tilemap).is_some_and(|tilemap| {
+            tilemap.overlaps(shape, alpha_threshold, materials, &self.chunks, &self.tiles)
+        })
+    }
+
+    pub fn sweep(
+        &self,
+        tilemap: Entity,
+        shape: &PixelShape,
+        motion: IVec2,
+        alpha_threshold: f32,
+        materials: &MaterialRegistry,
+    ) -> Option<PixelSweepHit> {
+        self.tilemap(tilemap)?.sweep(
+            shape,
+            motion,
+            alpha_threshold,
+            materials,
+            &self.chunks,
+            &self.tiles,
+        )
+    }
+}
@@ -0,0 +1,182 @@
+//! Tiles that cycle through frames, like flowing water or torches.
+//!
+//! Place them with [`Tilemap::set_animated_tile`](crate::tilemap::Tilemap::set_animated_tile). Every frame
+//! shares its pixels with the placed tile, so only tiles that switch frames are redrawn.
+//! Pixels changed on an animated tile are lost when it switches to the next frame.
+//!
+//! Animations aren't saved, since [`AnimatedTile`] isn't reflected. After a [save](crate::save) is loaded
+//! or a [streamed](crate::streaming) chunk is loaded again, its animated tiles stay on the frame they were saved with.
+use bevy::prelude::*;
+
+use crate::tile::Tile;
+
+#[derive(Clone, Debug)]
+pub struct AnimationFrame {
+    pub tile: Tile,
+    /// How long the frame is shown, in seconds
+    pub duration: f32,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct AnimatedTile {
+    frames: Vec<AnimationFrame>,
+    /// The length of a loop, in seconds
+    length: f32,
+    /// Seconds added to the animation clock for this tile, so identical tiles don't all move in step
+    pub phase: f32,
+    /// Paused tiles keep showing their current frame
+    pub paused: bool,
+    current: Option<usize>,
+}
+
+impl AnimatedTile {
+    /// Returns `None` if there are no frames, or the frames aren't all the same size
+    pub fn new(frames: Vec<AnimationFrame>) -> Option<Self> {
+        let size = frames.first()?.tile.size();
+        if frames.iter().any(|frame| frame.tile.size() != size) {
+            return None;
+        }
+
+        let length = frames.iter().map(|frame| frame.duration.max(0.0)).sum();
+
+        Some(Self {
+            frames,
+            length,
+            phase: 0.0,
+            paused: false,
+            current: None,
+        })
+    }
+
+    /// Shows every tile for `frame_duration` seconds, see [`AnimatedTile::new`]
+    pub fn from_tiles(tiles: impl IntoIterator<Item = Tile>, frame_duration: f32) -> Option<Self> {
+        Self::new(
+            tiles
+                .into_iter()
+                .map(|tile| AnimationFrame {
+                    tile,
+                    duration: frame_duration,
+                })
+                .collect(),
+        )
+    }
+
+    pub fn with_phase(mut self, phase: f32) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    /// The frame being shown, `None` until the animation first runs
+    pub fn current_frame(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// The frame shown `time` seconds into the animation, looping
+    pub fn frame_at(&self, time: f64) -> usize {
+        if self.length <= 0.0 {
+            return 0;
+        }
+
+        let mut time = time.rem_euclid(self.length as f64) as f32;
+        for (index, frame) in self.frames.iter().enumerate() {
+            let duration = frame.duration.max(0.0);
+            if time < duration {
+                return index;
+            }
+            time -= duration;
+        }
+
+        self.frames.len() - 1
+    }
+}
+
+/// The clock every [`AnimatedTile`] runs on
+#[derive(Resource, Clone, Debug, Default)]
+pub struct TileAnimations {
+    /// Pauses every animated tile
+    pub paused: bool,
+    elapsed: f64,
+}
+
+impl TileAnimations {
+    /// Seconds the animations have been running, not counting pauses
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+}
+
+/// Advances the animation clock, switching tiles whose frame changed
+pub fn tile_animation(
+    time: Res<Time>,
+    mut animations: ResMut<TileAnimations>,
+    mut tiles: Query<(&mut Tile, &mut AnimatedTile)>,
+) {
+    if animations.paused {
+        return;
+    }
+    animations.elapsed += time.delta_seconds_f64();
+
+    for (mut tile, mut animation) in &mut tiles {
+        if animation.paused {
+            continue;
+        }
+
+        let frame = animation.frame_at(animations.elapsed + animation.phase as f64);
+        if animation.current == Some(frame) {
+            continue;
+        }
+
+        animation.current = Some(frame);
+        tile.replace(&animation.frames[frame].tile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(duration: f32) -> AnimationFrame {
+        AnimationFrame {
+            tile: Tile::from_color(Color::WHITE, 2),
+            duration,
+        }
+    }
+
+    #[test]
+    fn frame_at_loops() {
+        let animation = AnimatedTile::new(vec![frame(1.0), frame(0.5), frame(0.0), frame(2.0)])
+            .expect("Frames are the same size");
+
+        assert_eq!(animation.frame_at(0.0), 0);
+        assert_eq!(animation.frame_at(0.99), 0);
+        assert_eq!(animation.frame_at(1.0), 1);
+        // Frames without a duration are skipped
+        assert_eq!(animation.frame_at(1.5), 3);
+        assert_eq!(animation.frame_at(3.49), 3);
+        assert_eq!(animation.frame_at(3.5), 0);
+        assert_eq!(animation.frame_at(-0.25), 3);
+    }
+
+    #[test]
+    fn frame_at_without_length() {
+        let animation = AnimatedTile::new(vec![frame(0.0), frame(-1.0)]).unwrap();
+        assert_eq!(animation.frame_at(10.0), 0);
+    }
+
+    #[test]
+    fn frames_must_match() {
+        assert!(AnimatedTile::new(vec![]).is_none());
+        assert!(AnimatedTile::from_tiles(
+            [
+                Tile::from_color(Color::WHITE, 2),
+                Tile::from_color(Color::WHITE, 4)
+            ],
+            1.0
+        )
+        .is_none());
+    }
+}
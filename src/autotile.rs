@@ -0,0 +1,414 @@
+//! Picking tile variants by their neighbors, for terrain edges and corners.
+//!
+//! Register an [`AutoTileSet`] in the [`AutoTileSets`] resource and place tiles with
+//! [`Tilemap::set_auto_tile`](crate::tilemap::Tilemap::set_auto_tile). Whenever a tile is set or deleted,
+//! the auto tiles at and around it pick the variant matching which of their neighbors use the same set.
+//! Neighbors in other chunks count as well, and tiles along the border of a chunk that is streamed in or out
+//! are updated too. Variants that aren't the tilemap's tile size are skipped.
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    chunk::Chunk,
+    dimensions::TilemapDimensions,
+    events::{ChunkLoadedEvent, ChunkUnloadedEvent, TileDeletedEvent, TileSetEvent},
+    position::{ChunkPos, TilePos},
+    tile::Tile,
+    tilemap::Tilemap,
+};
+
+/// Which neighbors use the same set, one bit per direction
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Neighbors(pub u8);
+
+impl Neighbors {
+    pub const NORTH: u8 = 1 << 0;
+    pub const NORTH_EAST: u8 = 1 << 1;
+    pub const EAST: u8 = 1 << 2;
+    pub const SOUTH_EAST: u8 = 1 << 3;
+    pub const SOUTH: u8 = 1 << 4;
+    pub const SOUTH_WEST: u8 = 1 << 5;
+    pub const WEST: u8 = 1 << 6;
+    pub const NORTH_WEST: u8 = 1 << 7;
+
+    /// The offset of every direction, in bit order, clockwise from north
+    pub const OFFSETS: [IVec2; 8] = [
+        IVec2::new(0, 1),
+        IVec2::new(1, 1),
+        IVec2::new(1, 0),
+        IVec2::new(1, -1),
+        IVec2::new(0, -1),
+        IVec2::new(-1, -1),
+        IVec2::new(-1, 0),
+        IVec2::new(-1, 1),
+    ];
+
+    pub fn contains(self, direction: u8) -> bool {
+        self.0 & direction == direction
+    }
+
+    /// The index into a 16 tile set, with north, east, south and west as bits 0 to 3
+    pub fn four_bit_index(self) -> usize {
+        [Self::NORTH, Self::EAST, Self::SOUTH, Self::WEST]
+            .into_iter()
+            .enumerate()
+            .filter(|(_, direction)| self.contains(*direction))
+            .map(|(bit, _)| 1 << bit)
+            .sum()
+    }
+
+    /// Drops corners whose two sides aren't both set, since they don't change the look of a blob tile
+    pub const fn blob_reduced(self) -> Self {
+        let mut mask = self.0;
+        let corners = [
+            (Self::NORTH_EAST, Self::NORTH, Self::EAST),
+            (Self::SOUTH_EAST, Self::SOUTH, Self::EAST),
+            (Self::SOUTH_WEST, Self::SOUTH, Self::WEST),
+            (Self::NORTH_WEST, Self::NORTH, Self::WEST),
+        ];
+
+        let mut index = 0;
+        while index < corners.len() {
+            let (corner, a, b) = corners[index];
+            if mask & a == 0 || mask & b == 0 {
+                mask &= !corner;
+            }
+            index += 1;
+        }
+
+        Self(mask)
+    }
+
+    /// The index into a 47 tile blob set.
+    /// Tiles are ordered by their reduced mask, from no neighbors to all of them.
+    pub fn blob_index(self) -> usize {
+        BLOB_INDICES[self.0 as usize] as usize
+    }
+}
+
+/// The blob index of every mask
+const BLOB_INDICES: [u8; 256] = blob_indices();
+
+const fn blob_indices() -> [u8; 256] {
+    let mut indices = [0; 256];
+
+    let mut next = 0;
+    let mut mask = 0;
+    while mask < 256 {
+        if Neighbors(mask as u8).blob_reduced().0 == mask as u8 {
+            indices[mask] = next;
+            next += 1;
+        }
+        mask += 1;
+    }
+
+    // Reducing only removes bits, so the reduced mask was numbered above
+    mask = 0;
+    while mask < 256 {
+        indices[mask] = indices[Neighbors(mask as u8).blob_reduced().0 as usize];
+        mask += 1;
+    }
+
+    indices
+}
+
+/// What a neighbor has to be for a [`AutoTileRule`] to match
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum NeighborRule {
+    #[default]
+    Any,
+    /// Uses the same set
+    Same,
+    /// Is empty or uses another set
+    Different,
+}
+
+#[derive(Clone, Debug)]
+pub struct AutoTileRule {
+    /// A rule for every neighbor, clockwise from north, like [`Neighbors::OFFSETS`]
+    pub pattern: [NeighborRule; 8],
+    pub tile: Tile,
+}
+
+impl AutoTileRule {
+    pub fn matches(&self, neighbors: Neighbors) -> bool {
+        self.pattern
+            .iter()
+            .enumerate()
+            .all(|(bit, rule)| match rule {
+                NeighborRule::Any => true,
+                NeighborRule::Same => neighbors.contains(1 << bit),
+                NeighborRule::Different => !neighbors.contains(1 << bit),
+            })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum AutoTileRules {
+    /// 16 tiles, indexed by [`Neighbors::four_bit_index`]
+    FourBit(Vec<Tile>),
+    /// 47 tiles, indexed by [`Neighbors::blob_index`]
+    Blob(Vec<Tile>),
+    /// The tile of the first matching rule, or the fallback if none match
+    Custom {
+        rules: Vec<AutoTileRule>,
+        fallback: Option<Tile>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct AutoTileSet {
+    pub name: String,
+    pub rules: AutoTileRules,
+}
+
+impl AutoTileSet {
+    pub fn new(name: impl Into<String>, rules: AutoTileRules) -> Self {
+        Self {
+            name: name.into(),
+            rules,
+        }
+    }
+
+    /// The variant for a tile with these neighbors, `None` if the set has no tile for them
+    pub fn variant(&self, neighbors: Neighbors) -> Option<&Tile> {
+        match &self.rules {
+            AutoTileRules::FourBit(tiles) => tiles.get(neighbors.four_bit_index()),
+            AutoTileRules::Blob(tiles) => tiles.get(neighbors.blob_index()),
+            AutoTileRules::Custom { rules, fallback } => rules
+                .iter()
+                .find(|rule| rule.matches(neighbors))
+                .map(|rule| &rule.tile)
+                .or(fallback.as_ref()),
+        }
+    }
+}
+
+/// Identifies a set in [`AutoTileSets`]
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AutoTileSetId(pub u32);
+
+/// Every auto tile set
+#[derive(Resource, Clone, Debug, Default)]
+pub struct AutoTileSets {
+    sets: Vec<AutoTileSet>,
+}
+
+impl AutoTileSets {
+    pub fn register(&mut self, set: AutoTileSet) -> AutoTileSetId {
+        self.sets.push(set);
+        AutoTileSetId(self.sets.len() as u32 - 1)
+    }
+
+    pub fn get(&self, id: AutoTileSetId) -> Option<&AutoTileSet> {
+        self.sets.get(id.0 as usize)
+    }
+
+    /// Finds a set by name
+    pub fn find(&self, name: &str) -> Option<AutoTileSetId> {
+        self.sets
+            .iter()
+            .position(|set| set.name == name)
+            .map(|index| AutoTileSetId(index as u32))
+    }
+}
+
+/// Makes the tile pick its variant from the set by its neighbors
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct AutoTile {
+    pub set: AutoTileSetId,
+    /// The neighbors the current variant was picked for
+    neighbors: Option<Neighbors>,
+}
+
+impl AutoTile {
+    pub fn new(set: AutoTileSetId) -> Self {
+        Self {
+            set,
+            neighbors: None,
+        }
+    }
+
+    /// The neighbors the current variant was picked for, `None` until a variant is picked
+    pub fn neighbors(&self) -> Option<Neighbors> {
+        self.neighbors
+    }
+}
+
+/// Tiles to recompute that weren't spawned yet when they were changed
+#[derive(Resource, Default)]
+pub struct AutoTileQueue {
+    pending: Vec<(Entity, TilePos)>,
+}
+
+/// Recomputes the variants of auto tiles at and around tiles that were set or deleted,
+/// and in and around chunks that were streamed in or out
+pub fn autotile_update(
+    (mut tile_set, mut tile_deleted, mut chunk_loaded, mut chunk_unloaded): (
+        EventReader<TileSetEvent>,
+        EventReader<TileDeletedEvent>,
+        EventReader<ChunkLoadedEvent>,
+        EventReader<ChunkUnloadedEvent>,
+    ),
+    mut queue: ResMut<AutoTileQueue>,
+    sets: Res<AutoTileSets>,
+    tilemaps: Query<&Tilemap>,
+    chunks: Query<&Chunk>,
+    mut tiles: Query<(&mut Tile, &mut AutoTile)>,
+    spawned: Query<(), With<Tile>>,
+) {
+    let changed = tile_set
+        .read()
+        .map(|event| (event.tilemap, event.loc))
+        .chain(tile_deleted.read().map(|event| (event.tilemap, event.loc)))
+        .collect::<Vec<_>>();
+
+    let mut dirty = queue.pending.drain(..).collect::<HashSet<_>>();
+    for (tilemap, loc) in changed {
+        dirty.insert((tilemap, loc));
+        for offset in Neighbors::OFFSETS {
+            dirty.insert((tilemap, loc + offset));
+        }
+    }
+
+    // Loaded chunks come with the variants they were saved with, so all of their tiles are checked
+    let streamed = chunk_loaded
+        .read()
+        .map(|event| (event.tilemap, event.loc))
+        .chain(
+            chunk_unloaded
+                .read()
+                .map(|event| (event.tilemap, event.loc)),
+        );
+    for (tilemap_entity, chunk_loc) in streamed {
+        let Ok(tilemap) = tilemaps.get(tilemap_entity) else {
+            continue;
+        };
+        dirty.extend(
+            chunk_with_border(chunk_loc, tilemap.dimensions()).map(|loc| (tilemap_entity, loc)),
+        );
+    }
+
+    for (tilemap_entity, loc) in dirty {
+        let Ok(tilemap) = tilemaps.get(tilemap_entity) else {
+            continue;
+        };
+        let Some(entity) = tilemap.get_tile(loc, &chunks) else {
+            continue;
+        };
+        let Ok((_, auto_tile)) = tiles.get(entity) else {
+            // Tiles placed this frame may not be spawned yet
+            if !spawned.contains(entity) {
+                queue.pending.push((tilemap_entity, loc));
+            }
+            continue;
+        };
+
+        let set = auto_tile.set;
+        let mut neighbors = Neighbors::default();
+        for (bit, offset) in Neighbors::OFFSETS.into_iter().enumerate() {
+            let same = tilemap
+                .get_tile(loc + offset, &chunks)
+                .and_then(|neighbor| tiles.get(neighbor).ok())
+                .is_some_and(|(_, neighbor)| neighbor.set == set);
+            if same {
+                neighbors.0 |= 1 << bit;
+            }
+        }
+
+        if auto_tile.neighbors == Some(neighbors) {
+            continue;
+        }
+        let Some(variant) = sets
+            .get(set)
+            .and_then(|set| set.variant(neighbors))
+            .filter(|variant| variant.size() == tilemap.dimensions().tile_size())
+        else {
+            continue;
+        };
+
+        let (mut tile, mut auto_tile) = tiles.get_mut(entity).expect("Tile was just found");
+        auto_tile.neighbors = Some(neighbors);
+        tile.replace(variant);
+    }
+}
+
+/// Every tile of the chunk and the ring of tiles around it
+fn chunk_with_border(
+    chunk: ChunkPos,
+    dimensions: TilemapDimensions,
+) -> impl Iterator<Item = TilePos> {
+    let origin = chunk.origin(dimensions);
+    let size = dimensions.chunk_size() as i32;
+
+    (-1..=size).flat_map(move |x| (-1..=size).map(move |y| origin + IVec2::new(x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blob_has_47_tiles() {
+        let indices = (0..=255u8)
+            .map(|mask| Neighbors(mask).blob_index())
+            .collect::<HashSet<_>>();
+        assert_eq!(indices.len(), 47);
+        assert_eq!(indices.iter().max(), Some(&46));
+
+        assert_eq!(Neighbors(0).blob_index(), 0);
+        assert_eq!(Neighbors(u8::MAX).blob_index(), 46);
+    }
+
+    #[test]
+    fn blob_ignores_loose_corners() {
+        let corner = Neighbors(Neighbors::NORTH | Neighbors::NORTH_EAST);
+        assert_eq!(corner.blob_reduced(), Neighbors(Neighbors::NORTH));
+        assert_eq!(
+            corner.blob_index(),
+            Neighbors(Neighbors::NORTH).blob_index()
+        );
+
+        let filled = Neighbors(Neighbors::NORTH | Neighbors::EAST | Neighbors::NORTH_EAST);
+        assert_eq!(filled.blob_reduced(), filled);
+        assert_ne!(
+            filled.blob_index(),
+            Neighbors(Neighbors::NORTH | Neighbors::EAST).blob_index()
+        );
+    }
+
+    #[test]
+    fn four_bit_ignores_corners() {
+        assert_eq!(Neighbors(Neighbors::NORTH).four_bit_index(), 1);
+        assert_eq!(Neighbors(Neighbors::WEST).four_bit_index(), 8);
+        assert_eq!(Neighbors(Neighbors::NORTH_EAST).four_bit_index(), 0);
+        assert_eq!(Neighbors(u8::MAX).four_bit_index(), 15);
+    }
+
+    #[test]
+    fn custom_rules() {
+        let red = Tile::from_color(Color::RED, 2);
+        let blue = Tile::from_color(Color::BLUE, 2);
+        let mut pattern = [NeighborRule::Any; 8];
+        pattern[0] = NeighborRule::Same;
+        pattern[4] = NeighborRule::Different;
+        let set = AutoTileSet::new(
+            "column top",
+            AutoTileRules::Custom {
+                rules: vec![AutoTileRule {
+                    pattern,
+                    tile: red.clone(),
+                }],
+                fallback: Some(blue.clone()),
+            },
+        );
+
+        assert_eq!(set.variant(Neighbors(Neighbors::NORTH)), Some(&red));
+        assert_eq!(
+            set.variant(Neighbors(Neighbors::NORTH | Neighbors::SOUTH)),
+            Some(&blue)
+        );
+    }
+}
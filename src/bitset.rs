@@ -0,0 +1,130 @@
+/// A fixed size set of indices, stored one bit per index
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BitSet {
+    words: Vec<u64>,
+    count: usize,
+}
+
+impl BitSet {
+    pub fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+            count: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Returns true if the index wasn't already in the set
+    pub fn insert(&mut self, index: usize) -> bool {
+        let word = &mut self.words[index / 64];
+        let bit = 1 << (index % 64);
+        if *word & bit != 0 {
+            return false;
+        }
+
+        *word |= bit;
+        self.count += 1;
+        true
+    }
+
+    /// Inserts every index in `start..start + len`
+    pub fn insert_range(&mut self, start: usize, len: usize) {
+        let end = start + len;
+        let mut index = start;
+        while index < end {
+            let offset = index % 64;
+            let bits = (64 - offset).min(end - index);
+            let mask = if bits == 64 {
+                u64::MAX
+            } else {
+                ((1u64 << bits) - 1) << offset
+            };
+
+            let word = &mut self.words[index / 64];
+            self.count += (mask & !*word).count_ones() as usize;
+            *word |= mask;
+
+            index += bits;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        if self.count == 0 {
+            return;
+        }
+
+        self.words.iter_mut().for_each(|word| *word = 0);
+        self.count = 0;
+    }
+
+    /// Iterates over every index in the set, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| **word != 0)
+            .flat_map(|(word_index, word)| {
+                let mut word = *word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    Some(word_index * 64 + bit)
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_clear() {
+        let mut set = BitSet::new(100);
+        assert!(set.is_empty());
+        assert!(set.insert(3));
+        assert!(!set.insert(3));
+        assert!(set.insert(99));
+        assert!(set.contains(3) && set.contains(99) && !set.contains(4));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![3, 99]);
+
+        set.clear();
+        assert!(set.is_empty());
+        assert_eq!(set.iter().count(), 0);
+    }
+
+    #[test]
+    fn insert_range_across_words() {
+        let mut set = BitSet::new(200);
+        set.insert(70);
+        set.insert_range(60, 70);
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            (60..130).collect::<Vec<_>>()
+        );
+        assert_eq!(set.count, 70);
+
+        // A full, aligned word
+        let mut set = BitSet::new(128);
+        set.insert_range(64, 64);
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            (64..128).collect::<Vec<_>>()
+        );
+
+        let mut set = BitSet::new(10);
+        set.insert_range(5, 0);
+        assert!(set.is_empty());
+    }
+}
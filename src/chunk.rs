@@ -1,54 +1,71 @@
 use bevy::{prelude::*, render::render_resource::Extent3d};
 
 use crate::{
-    tile::{DeletingTile, Tile},
-    CHUNK_SIZE, TILE_SIZE,
+    bitset::BitSet,
+    dimensions::TilemapDimensions,
+    position::{ChunkPos, LocalTilePos, PixelPos},
+    tile::{ChunkTileLocation, DeletingTile, Tile},
 };
 
+/// The location of a chunk in its tilemap
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLocation(pub ChunkPos);
+
 #[derive(Bundle)]
 pub struct ChunkBundle {
     sprite: SpriteBundle,
     chunk: Chunk,
+    location: ChunkLocation,
 }
 
 impl ChunkBundle {
-    pub fn new(loc: IVec2, chunk: Chunk) -> Self {
+    pub fn new(loc: ChunkPos, chunk: Chunk) -> Self {
+        let dimensions = chunk.dimensions;
+
         Self {
             sprite: SpriteBundle {
                 texture: chunk.image_handle.clone_weak(),
                 transform: Transform::from_xyz(
-                    loc.x as f32 * CHUNK_SIZE as f32,
-                    loc.y as f32 * CHUNK_SIZE as f32,
+                    loc.0.x as f32 * dimensions.chunk_size() as f32,
+                    loc.0.y as f32 * dimensions.chunk_size() as f32,
                     0.0,
                 )
-                .with_scale(Vec3::splat(1.0 / TILE_SIZE as f32)),
+                .with_scale(Vec3::splat(1.0 / dimensions.tile_size() as f32)),
                 ..Default::default()
             },
             chunk,
+            location: ChunkLocation(loc),
         }
     }
 }
 
 #[derive(Component)]
 pub struct Chunk {
-    tiles: [[Option<Entity>; CHUNK_SIZE]; CHUNK_SIZE],
-    image: Image,
+    dimensions: TilemapDimensions,
+    /// Column major, `tiles[x * chunk_size + y]`
+    tiles: Vec<Option<Entity>>,
     image_handle: Handle<Image>,
-    dirty_tiles: Vec<IVec2>,
+    /// Tiles with at least one dirty pixel, indexed the same as `tiles`
+    dirty_tiles: BitSet,
+    /// Pixels that need to be redrawn, indexed by `y * width + x` on the texture
+    dirty_pixels: BitSet,
+    /// Increased every time a tile or pixel is marked dirty
+    revision: u64,
 }
 
 impl Chunk {
-    pub fn new(images: &mut ResMut<Assets<Image>>) -> Self {
-        let mut data = vec![];
+    pub fn new(images: &mut Assets<Image>) -> Self {
+        Self::with_dimensions(images, TilemapDimensions::default())
+    }
 
-        for _ in 0..((CHUNK_SIZE * CHUNK_SIZE) * TILE_SIZE * TILE_SIZE) {
-            data.append(&mut Color::rgba(0.0, 0.0, 0.0, 0.0).as_rgba_u8().to_vec());
-        }
+    pub fn with_dimensions(images: &mut Assets<Image>, dimensions: TilemapDimensions) -> Self {
+        let pixel_count = dimensions.chunk_pixels() * dimensions.chunk_pixels();
+        let data = Color::NONE.as_rgba_u8().repeat(pixel_count);
 
         let image = Image::new(
             Extent3d {
-                width: CHUNK_SIZE as u32 * TILE_SIZE as u32,
-                height: CHUNK_SIZE as u32 * TILE_SIZE as u32,
+                width: dimensions.chunk_pixels() as u32,
+                height: dimensions.chunk_pixels() as u32,
                 ..default()
             },
             bevy::render::render_resource::TextureDimension::D2,
@@ -57,38 +74,77 @@ impl Chunk {
         );
 
         Self {
-            tiles: [[None; CHUNK_SIZE]; CHUNK_SIZE],
-            image: image.clone(),
+            dimensions,
+            tiles: vec![None; dimensions.chunk_size() * dimensions.chunk_size()],
             image_handle: images.add(image),
-            dirty_tiles: vec![],
+            dirty_tiles: BitSet::new(dimensions.chunk_size() * dimensions.chunk_size()),
+            dirty_pixels: BitSet::new(pixel_count),
+            revision: 0,
         }
     }
 
-    pub fn get_tile(&self, loc: IVec2) -> Option<Entity> {
-        if !verify_chunk_loc(loc) {
-            return None;
-        }
+    pub fn dimensions(&self) -> TilemapDimensions {
+        self.dimensions
+    }
+
+    pub fn get_tile(&self, loc: LocalTilePos) -> Option<Entity> {
+        self.tiles[self.tile_index(loc)?]
+    }
+
+    /// Iterates over the location and entity of every tile in the chunk
+    pub fn tiles(&self) -> impl Iterator<Item = (LocalTilePos, Entity)> + '_ {
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, tile)| tile.map(|tile| (self.tile_loc(index), tile)))
+    }
+
+    /// Changes whenever a tile or pixel of the chunk is marked to be redrawn,
+    /// so systems can tell if the chunk's contents changed since they last looked at it
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn image_handle(&self) -> &Handle<Image> {
+        &self.image_handle
+    }
+
+    /// Places an already parented tile entity without going through commands.
+    /// Any tile already at the location is left alone.
+    pub(crate) fn insert_tile_entity(&mut self, loc: LocalTilePos, entity: Entity) {
+        let Some(index) = self.tile_index(loc) else {
+            return;
+        };
 
-        self.tiles[loc.x as usize][loc.y as usize]
+        self.tiles[index] = Some(entity);
+        self.update_tile(loc);
     }
 
     pub fn set_tile(
         &mut self,
         my_entity: Entity,
-        loc: IVec2,
+        loc: LocalTilePos,
         tile: Tile,
         additional_components: impl Bundle,
         commands: &mut Commands,
     ) {
-        if !verify_chunk_loc(loc) {
+        let Some(index) = self.tile_index(loc) else {
+            return;
+        };
+        if tile.size() != self.dimensions.tile_size() {
+            warn!(
+                "Skipped placing a {} pixel tile in a chunk with {} pixel tiles",
+                tile.size(),
+                self.dimensions.tile_size()
+            );
             return;
         }
 
         self.delete_tile(loc, commands);
 
-        self.tiles[loc.x as usize][loc.y as usize] = Some(
+        self.tiles[index] = Some(
             commands
-                .spawn((tile, additional_components))
+                .spawn((tile, ChunkTileLocation(loc), additional_components))
                 .set_parent(my_entity)
                 .id(),
         );
@@ -96,134 +152,171 @@ impl Chunk {
         self.update_tile(loc)
     }
 
-    pub fn set_tile_entity(&mut self, loc: IVec2, entity: Entity, commands: &mut Commands) {
+    pub fn set_tile_entity(&mut self, loc: LocalTilePos, entity: Entity, commands: &mut Commands) {
+        let Some(index) = self.tile_index(loc) else {
+            return;
+        };
+
         self.delete_tile(loc, commands);
-        self.tiles[loc.x as usize][loc.y as usize] = Some(entity);
+        self.tiles[index] = Some(entity);
+        commands.entity(entity).insert(ChunkTileLocation(loc));
 
         self.update_tile(loc)
     }
 
-    pub fn delete_tile(&mut self, loc: IVec2, commands: &mut Commands) {
-        if let Some(entity) = self.get_tile(loc) {
+    pub fn delete_tile(&mut self, loc: LocalTilePos, commands: &mut Commands) {
+        let Some(index) = self.tile_index(loc) else {
+            return;
+        };
+
+        if let Some(entity) = self.tiles[index].take() {
             commands.entity(entity).insert(DeletingTile);
-            self.tiles[loc.x as usize][loc.y as usize] = None;
             self.update_tile(loc);
         }
     }
 
-    pub fn delete_unmarked(&mut self, loc: IVec2, commands: &mut Commands) {
-        if let Some(entity) = self.get_tile(loc) {
+    pub fn delete_unmarked(&mut self, loc: LocalTilePos, commands: &mut Commands) {
+        let Some(index) = self.tile_index(loc) else {
+            return;
+        };
+
+        if let Some(entity) = self.tiles[index].take() {
             commands.entity(entity).despawn_recursive();
-            self.tiles[loc.x as usize][loc.y as usize] = None;
             self.update_tile(loc);
         }
     }
 
-    pub fn update_tile(&mut self, loc: IVec2) {
-        if !verify_chunk_loc(loc) {
+    /// Marks every pixel of the tile to be redrawn
+    pub fn update_tile(&mut self, loc: LocalTilePos) {
+        let Some(index) = self.tile_index(loc) else {
             return;
+        };
+        self.dirty_tiles.insert(index);
+        self.revision = self.revision.wrapping_add(1);
+
+        let tile_size = self.dimensions.tile_size();
+        for pixel_y in 0..tile_size {
+            let start = self.texture_index(loc, PixelPos::new(0, pixel_y as i32));
+            self.dirty_pixels.insert_range(start, tile_size);
         }
-        if self.dirty_tiles.contains(&loc) {
+    }
+
+    /// Marks a single pixel of the tile to be redrawn
+    pub fn update_pixel(&mut self, loc: LocalTilePos, pixel: PixelPos) {
+        let Some(index) = self.tile_index(loc) else {
+            return;
+        };
+        if !pixel.in_bounds(self.dimensions) {
             return;
         }
 
-        self.dirty_tiles.push(loc)
+        self.dirty_tiles.insert(index);
+        self.revision = self.revision.wrapping_add(1);
+        self.dirty_pixels.insert(self.texture_index(loc, pixel));
     }
 
-    pub fn update_texture(
-        &mut self,
-        images: &mut ResMut<Assets<Image>>,
-        tiles: &mut Query<&mut Tile>,
-    ) {
+    /// Writes the dirty pixels into the chunk's image in place
+    pub fn update_texture(&mut self, images: &mut ResMut<Assets<Image>>, tiles: &Query<&Tile>) {
         if self.dirty_tiles.is_empty() {
             return;
         }
 
-        let mut data = self.image.data.clone();
-
-        for loc in &self.dirty_tiles {
-            if let Some(tile) = self.get_tile(*loc) {
-                if let Ok(tile) = tiles.get(tile) {
-                    for pixel_x in 0..TILE_SIZE {
-                        for pixel_y in 0..TILE_SIZE {
-                            // Inverse of y * pixels per tile + the current pixel
-                            let pixel_index_y =
-                                ((CHUNK_SIZE - 1 - loc.y as usize) * TILE_SIZE) + pixel_y;
-                            // x * pixels per tile + the current pixel
-                            let pixel_index_x = (loc.x as usize * TILE_SIZE) + pixel_x;
-
-                            let color = tile
-                                .get_pixel(IVec2::new(pixel_x as i32, pixel_y as i32))
-                                .expect("Pixel should be in range")
-                                .as_rgba_u8();
-
-                            // Update the color on the texture with tile texture
-                            data[pixel_index_y * (CHUNK_SIZE * TILE_SIZE) * 4
-                                + pixel_index_x * 4] = color[0];
-
-                            data[(pixel_index_y * (CHUNK_SIZE * TILE_SIZE) * 4
-                                + pixel_index_x * 4)
-                                + 1] = color[1];
-
-                            data[(pixel_index_y * (CHUNK_SIZE * TILE_SIZE) * 4
-                                + pixel_index_x * 4)
-                                + 2] = color[2];
-
-                            data[(pixel_index_y * (CHUNK_SIZE * TILE_SIZE) * 4
-                                + pixel_index_x * 4)
-                                + 3] = color[3];
-                        }
-                    }
-                }
-            } else {
-                // It's dirty, but the tile doesn't exist, so clear the tile's color.
-                for pixel_x in 0..TILE_SIZE {
-                    for pixel_y in 0..TILE_SIZE {
-                        // Inverse of y * pixels per tile + the current pixel
-                        let pixel_index_y =
-                            ((CHUNK_SIZE - 1 - loc.y as usize) * TILE_SIZE) + pixel_y;
-
-                        // x * pixels per tile + the current pixel
-                        let pixel_index_x = (loc.x as usize * TILE_SIZE) + pixel_x;
-
-                        // Update the color on the texture with tile texture
-                        data[pixel_index_y * (CHUNK_SIZE * TILE_SIZE) * 4 + pixel_index_x * 4] = 0;
+        let Some(image) = images.get_mut(&self.image_handle) else {
+            return;
+        };
 
-                        data[(pixel_index_y * (CHUNK_SIZE * TILE_SIZE) * 4 + pixel_index_x * 4)
-                            + 1] = 0;
+        let tile_size = self.dimensions.tile_size();
 
-                        data[(pixel_index_y * (CHUNK_SIZE * TILE_SIZE) * 4 + pixel_index_x * 4)
-                            + 2] = 0;
+        for index in self.dirty_tiles.iter() {
+            let loc = self.tile_loc(index);
+            let tile = self.tiles[index].and_then(|tile| tiles.get(tile).ok());
 
-                        data[(pixel_index_y * (CHUNK_SIZE * TILE_SIZE) * 4 + pixel_index_x * 4)
-                            + 3] = 0;
+            for pixel_y in 0..tile_size {
+                for pixel_x in 0..tile_size {
+                    let pixel = PixelPos::new(pixel_x as i32, pixel_y as i32);
+                    let texture_index = self.texture_index(loc, pixel);
+                    if !self.dirty_pixels.contains(texture_index) {
+                        continue;
                     }
+
+                    // It's dirty, but the tile doesn't exist, so clear the pixel's color.
+                    let color = match tile {
+                        Some(tile) => tile
+                            .get_pixel(pixel)
+                            .expect("Pixel should be in range")
+                            .as_rgba_u8(),
+                        None => [0; 4],
+                    };
+
+                    let data_index = texture_index * 4;
+                    image.data[data_index..data_index + 4].copy_from_slice(&color);
                 }
             }
         }
 
         self.dirty_tiles.clear();
+        self.dirty_pixels.clear();
+    }
 
-        self.image.data = data;
+    /// The index of a tile's pixel on the chunk's texture, which is stored top to bottom
+    fn texture_index(&self, loc: LocalTilePos, pixel: PixelPos) -> usize {
+        let chunk_size = self.dimensions.chunk_size();
+        let tile_size = self.dimensions.tile_size();
 
-        images.insert(self.image_handle.clone(), self.image.clone());
+        // Inverse of y * pixels per tile + the current pixel
+        let y = (chunk_size - 1 - loc.0.y as usize) * tile_size + pixel.0.y as usize;
+        // x * pixels per tile + the current pixel
+        let x = loc.0.x as usize * tile_size + pixel.0.x as usize;
+
+        y * self.dimensions.chunk_pixels() + x
     }
-}
 
-fn verify_chunk_loc(loc: IVec2) -> bool {
-    if loc.x < 0 || loc.x >= CHUNK_SIZE as i32 || loc.y < 0 || loc.y >= CHUNK_SIZE as i32 {
-        return false;
+    fn tile_index(&self, loc: LocalTilePos) -> Option<usize> {
+        if !loc.in_bounds(self.dimensions) {
+            return None;
+        }
+
+        Some(loc.0.x as usize * self.dimensions.chunk_size() + loc.0.y as usize)
+    }
+
+    /// The inverse of `tile_index`
+    fn tile_loc(&self, index: usize) -> LocalTilePos {
+        let chunk_size = self.dimensions.chunk_size();
+        LocalTilePos::new((index / chunk_size) as i32, (index % chunk_size) as i32)
     }
-    true
 }
 
 pub fn chunk_texture_update(
     mut images: ResMut<Assets<Image>>,
-    mut tiles: Query<&mut Tile>,
+    tiles: Query<&Tile>,
     mut chunks: Query<&mut Chunk>,
 ) {
     for mut chunk in &mut chunks {
-        chunk.update_texture(&mut images, &mut tiles)
+        chunk.update_texture(&mut images, &tiles)
+    }
+}
+
+/// Marks tiles whose [`Tile`] component changed as dirty in their parent chunk
+pub fn chunk_tile_change_detection(
+    mut tiles: Query<(Entity, &Parent, &ChunkTileLocation, &mut Tile), Changed<Tile>>,
+    mut chunks: Query<&mut Chunk>,
+) {
+    for (entity, parent, location, mut tile) in &mut tiles {
+        let Ok(mut chunk) = chunks.get_mut(parent.get()) else {
+            continue;
+        };
+        if chunk.get_tile(location.0) != Some(entity) {
+            continue;
+        }
+
+        match tile.bypass_change_detection().take_changed_pixels() {
+            Some(pixels) => {
+                for pixel in pixels {
+                    chunk.update_pixel(location.0, pixel);
+                }
+            }
+            None => chunk.update_tile(location.0),
+        }
     }
 }
 
@@ -235,3 +328,102 @@ pub fn chunk_deleter(
         commands.entity(entity).despawn_recursive();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    /// A single chunk of 2 by 2 tiles of 2 pixels, with a red tile at `(0, 0)` that was already drawn
+    fn setup() -> (World, Entity, Handle<Image>) {
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+
+        let dimensions = TilemapDimensions::new(2, 2);
+        let chunk = Chunk::with_dimensions(&mut world.resource_mut(), dimensions);
+        let image = chunk.image_handle().clone();
+        let chunk = world.spawn(chunk).id();
+        let tile = world
+            .spawn((
+                Tile::from_color(Color::RED, 2),
+                ChunkTileLocation(LocalTilePos::ZERO),
+            ))
+            .set_parent(chunk)
+            .id();
+        world
+            .get_mut::<Chunk>(chunk)
+            .unwrap()
+            .insert_tile_entity(LocalTilePos::ZERO, tile);
+        redraw(&mut world);
+
+        (world, tile, image)
+    }
+
+    fn redraw(world: &mut World) {
+        world.run_system_once(chunk_tile_change_detection);
+        world.run_system_once(chunk_texture_update);
+    }
+
+    /// The colors of tile `(0, 0)`, which is the bottom left of the texture, row major from the top
+    fn tile_colors(world: &World, image: &Handle<Image>) -> Vec<[u8; 4]> {
+        let image = world.resource::<Assets<Image>>().get(image).unwrap();
+        [(0, 2), (1, 2), (0, 3), (1, 3)]
+            .into_iter()
+            .map(|(x, y)| {
+                let index = (y * 4 + x) * 4;
+                image.data[index..index + 4].try_into().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn redraws_changed_pixels() {
+        let (mut world, tile, image) = setup();
+        assert_eq!(tile_colors(&world, &image), vec![[255, 0, 0, 255]; 4]);
+
+        // Only the changed pixel is redrawn, so a pixel written straight to the texture stays
+        let handle = image.clone();
+        world
+            .resource_mut::<Assets<Image>>()
+            .get_mut(&handle)
+            .unwrap()
+            .data[(3 * 4 + 1) * 4..(3 * 4 + 1) * 4 + 4]
+            .copy_from_slice(&[0, 0, 0, 0]);
+        world
+            .get_mut::<Tile>(tile)
+            .unwrap()
+            .set_pixel(PixelPos::ZERO, Color::GREEN);
+        redraw(&mut world);
+
+        assert_eq!(
+            tile_colors(&world, &image),
+            vec![
+                [0, 255, 0, 255],
+                [255, 0, 0, 255],
+                [255, 0, 0, 255],
+                [0, 0, 0, 0]
+            ]
+        );
+    }
+
+    #[test]
+    fn redraws_reassigned_tiles() {
+        let (mut world, tile, image) = setup();
+
+        let mut tile = world.get_mut::<Tile>(tile).unwrap();
+        *tile = Tile::from_color(Color::BLUE, 2);
+        tile.set_pixel(PixelPos::ZERO, Color::GREEN);
+        redraw(&mut world);
+
+        assert_eq!(
+            tile_colors(&world, &image),
+            vec![
+                [0, 255, 0, 255],
+                [0, 0, 255, 255],
+                [0, 0, 255, 255],
+                [0, 0, 255, 255]
+            ]
+        );
+    }
+}
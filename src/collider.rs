@@ -0,0 +1,366 @@
+//! Polygon outlines of the solid pixels of every chunk, for any physics backend to build colliders from.
+//!
+//! Add [`TilemapColliders`] to a tilemap entity, and each of its chunks gets a [`ChunkCollider`]
+//! that is regenerated whenever the chunk changes.
+//! Points are in world pixel space, where world pixel `(x, y)` covers `x..x + 1` and `y..y + 1`.
+//! Pixels without a material are part of the outline when their alpha is above [`TilemapColliders::alpha_threshold`].
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    chunk::Chunk,
+    material::MaterialRegistry,
+    position::{ChunkPos, PixelPos},
+    tile::Tile,
+    tilemap::Tilemap,
+};
+
+/// Generates a [`ChunkCollider`] for every chunk of the tilemap
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct TilemapColliders {
+    /// Pixels without a material are solid when their alpha is above this
+    pub alpha_threshold: f32,
+    /// How far, in pixels, a simplified outline may stray from the pixel edges.
+    /// Zero keeps the exact outline.
+    pub tolerance: f32,
+}
+
+impl Default for TilemapColliders {
+    fn default() -> Self {
+        Self {
+            alpha_threshold: 0.0,
+            tolerance: 0.5,
+        }
+    }
+}
+
+/// The outlines of the solid pixels of a chunk.
+///
+/// Outer outlines wind counter clockwise and the outlines of holes wind clockwise.
+/// Pixels outside of the chunk are treated as empty, so outlines are closed at the chunk's border.
+#[derive(Component, Clone, Debug, Default)]
+pub struct ChunkCollider {
+    polygons: Vec<Vec<Vec2>>,
+    /// The [`Chunk::revision`] the outlines were generated from
+    revision: u64,
+}
+
+impl ChunkCollider {
+    pub fn polygons(&self) -> &[Vec<Vec2>] {
+        &self.polygons
+    }
+}
+
+/// Builds the outlines of a chunk's solid pixels
+pub fn chunk_outlines(
+    chunk: &Chunk,
+    loc: ChunkPos,
+    tiles: &Query<&Tile>,
+    settings: TilemapColliders,
+    materials: &MaterialRegistry,
+) -> Vec<Vec<Vec2>> {
+    let dimensions = chunk.dimensions();
+    let solid = solid_pixels(chunk, tiles, settings.alpha_threshold, materials);
+    let origin = (loc.origin(dimensions).0 * dimensions.tile_size() as i32).as_vec2();
+
+    trace_outlines(&solid, dimensions.chunk_pixels())
+        .into_iter()
+        .map(|outline| simplify(&outline, settings.tolerance))
+        .filter(|outline| outline.len() >= 3)
+        .map(|outline| {
+            outline
+                .into_iter()
+                .map(|point| origin + point.as_vec2())
+                .collect()
+        })
+        .collect()
+}
+
+/// Whether each pixel of the chunk is solid, indexed by `y * width + x` with y going up
+fn solid_pixels(
+    chunk: &Chunk,
+    tiles: &Query<&Tile>,
+    alpha_threshold: f32,
+    materials: &MaterialRegistry,
+) -> Vec<bool> {
+    let tile_size = chunk.dimensions().tile_size();
+    let width = chunk.dimensions().chunk_pixels();
+    let mut solid = vec![false; width * width];
+
+    for (loc, entity) in chunk.tiles() {
+        let Ok(tile) = tiles.get(entity) else {
+            continue;
+        };
+
+        for pixel_y in 0..tile_size {
+            for pixel_x in 0..tile_size {
+                let pixel = PixelPos::new(pixel_x as i32, pixel_y as i32);
+                if !tile.is_solid(pixel, alpha_threshold, materials) {
+                    continue;
+                }
+
+                let x = loc.0.x as usize * tile_size + pixel_x;
+                let y = loc.0.y as usize * tile_size + tile_size - 1 - pixel_y;
+                solid[y * width + x] = true;
+            }
+        }
+    }
+
+    solid
+}
+
+/// Follows the edges between solid and empty pixels, keeping solid pixels on the left.
+/// Returns the corners of every outline.
+fn trace_outlines(solid: &[bool], width: usize) -> Vec<Vec<IVec2>> {
+    let is_solid = |x: i32, y: i32| {
+        x >= 0
+            && y >= 0
+            && (x as usize) < width
+            && (y as usize) < width
+            && solid[y as usize * width + x as usize]
+    };
+
+    // Every edge by its start, and the direction it goes in
+    let mut edges: HashMap<IVec2, Vec<IVec2>> = HashMap::new();
+    for y in 0..width as i32 {
+        for x in 0..width as i32 {
+            if !is_solid(x, y) {
+                continue;
+            }
+
+            let mut edge = |start: IVec2, direction: IVec2| {
+                edges.entry(start).or_default().push(direction);
+            };
+            if !is_solid(x, y - 1) {
+                edge(IVec2::new(x, y), IVec2::X);
+            }
+            if !is_solid(x + 1, y) {
+                edge(IVec2::new(x + 1, y), IVec2::Y);
+            }
+            if !is_solid(x, y + 1) {
+                edge(IVec2::new(x + 1, y + 1), IVec2::NEG_X);
+            }
+            if !is_solid(x - 1, y) {
+                edge(IVec2::new(x, y + 1), IVec2::NEG_Y);
+            }
+        }
+    }
+
+    let mut starts = edges.keys().copied().collect::<Vec<_>>();
+    // Hash map order isn't stable, sort so the outlines are deterministic
+    starts.sort_by_key(|start| (start.y, start.x));
+
+    let mut outlines = vec![];
+    for start in starts {
+        while let Some(first) = take_edge(&mut edges, start, None) {
+            let mut outline = vec![start];
+            let mut point = start + first;
+            let mut direction = first;
+
+            while point != start {
+                let next = take_edge(&mut edges, point, Some(direction))
+                    .expect("Every edge is part of a closed outline");
+                if next != direction {
+                    outline.push(point);
+                }
+                point += next;
+                direction = next;
+            }
+
+            // Drop the start if the outline goes straight through it
+            if direction == first {
+                outline.remove(0);
+            }
+            outlines.push(outline);
+        }
+    }
+
+    outlines
+}
+
+/// Takes an edge starting at `point`.
+/// Where two outlines touch at a corner, turning left keeps them apart.
+fn take_edge(
+    edges: &mut HashMap<IVec2, Vec<IVec2>>,
+    point: IVec2,
+    incoming: Option<IVec2>,
+) -> Option<IVec2> {
+    let directions = edges.get_mut(&point)?;
+    let index = incoming
+        .and_then(|incoming| {
+            let left = IVec2::new(-incoming.y, incoming.x);
+            directions.iter().position(|direction| *direction == left)
+        })
+        .unwrap_or(0);
+
+    let direction = directions.swap_remove(index);
+    if directions.is_empty() {
+        edges.remove(&point);
+    }
+
+    Some(direction)
+}
+
+/// Ramer-Douglas-Peucker simplification of a closed outline
+fn simplify(outline: &[IVec2], tolerance: f32) -> Vec<IVec2> {
+    if tolerance <= 0.0 || outline.len() <= 3 {
+        return outline.to_vec();
+    }
+
+    // Split the outline at the point furthest from the first, so both halves are open lines
+    let first = outline[0].as_vec2();
+    let (split, _) = outline
+        .iter()
+        .enumerate()
+        .map(|(index, point)| (index, point.as_vec2().distance_squared(first)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .expect("Outline isn't empty");
+
+    let mut closed = outline.to_vec();
+    closed.push(outline[0]);
+
+    let mut keep = vec![false; closed.len()];
+    keep[0] = true;
+    keep[split] = true;
+    simplify_line(&closed, 0, split, tolerance, &mut keep);
+    simplify_line(&closed, split, closed.len() - 1, tolerance, &mut keep);
+
+    outline
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(point, _)| *point)
+        .collect()
+}
+
+fn simplify_line(points: &[IVec2], start: usize, end: usize, tolerance: f32, keep: &mut [bool]) {
+    if end <= start + 1 {
+        return;
+    }
+
+    let (a, b) = (points[start].as_vec2(), points[end].as_vec2());
+    let (furthest, distance) = (start + 1..end)
+        .map(|index| (index, distance_to_line(points[index].as_vec2(), a, b)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .expect("Line has inner points");
+
+    if distance > tolerance {
+        keep[furthest] = true;
+        simplify_line(points, start, furthest, tolerance, keep);
+        simplify_line(points, furthest, end, tolerance, keep);
+    }
+}
+
+fn distance_to_line(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let line = b - a;
+    if line == Vec2::ZERO {
+        return point.distance(a);
+    }
+
+    (line.perp_dot(point - a) / line.length()).abs()
+}
+
+/// Regenerates the [`ChunkCollider`]s of chunks that changed
+pub fn chunk_collider_update(
+    mut commands: Commands,
+    tilemaps: Query<(&Tilemap, Ref<TilemapColliders>)>,
+    mut chunks: Query<(&Chunk, Option<&mut ChunkCollider>)>,
+    tiles: Query<&Tile>,
+    materials: Res<MaterialRegistry>,
+) {
+    for (tilemap, settings) in &tilemaps {
+        let rebuild = settings.is_changed() || materials.is_changed();
+
+        for (loc, entity) in tilemap.chunks() {
+            let Ok((chunk, collider)) = chunks.get_mut(entity) else {
+                continue;
+            };
+
+            let revision = chunk.revision();
+            match collider {
+                Some(collider) if collider.revision == revision && !rebuild => {}
+                Some(mut collider) => {
+                    collider.polygons = chunk_outlines(chunk, loc, &tiles, *settings, &materials);
+                    collider.revision = revision;
+                }
+                None => {
+                    commands.entity(entity).insert(ChunkCollider {
+                        polygons: chunk_outlines(chunk, loc, &tiles, *settings, &materials),
+                        revision,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a solid grid from rows, the first row being the top
+    fn grid(rows: &[&str]) -> (Vec<bool>, usize) {
+        let width = rows.len();
+        let mut solid = vec![false; width * width];
+        for (row_index, row) in rows.iter().enumerate() {
+            let y = width - 1 - row_index;
+            for (x, pixel) in row.chars().enumerate() {
+                solid[y * width + x] = pixel == '#';
+            }
+        }
+
+        (solid, width)
+    }
+
+    #[test]
+    fn trace_square() {
+        let (solid, width) = grid(&["....", ".##.", ".##.", "...."]);
+        assert_eq!(
+            trace_outlines(&solid, width),
+            vec![vec![
+                IVec2::new(1, 1),
+                IVec2::new(3, 1),
+                IVec2::new(3, 3),
+                IVec2::new(1, 3)
+            ]]
+        );
+    }
+
+    #[test]
+    fn trace_hole() {
+        let (solid, width) = grid(&["###", "#.#", "###"]);
+        let outlines = trace_outlines(&solid, width);
+        assert_eq!(outlines.len(), 2);
+        assert!(outlines.iter().all(|outline| outline.len() == 4));
+        assert!(outlines.contains(&vec![
+            IVec2::new(1, 1),
+            IVec2::new(1, 2),
+            IVec2::new(2, 2),
+            IVec2::new(2, 1)
+        ]));
+    }
+
+    #[test]
+    fn trace_diagonal_pixels_apart() {
+        let (solid, width) = grid(&[".#", "#."]);
+        let outlines = trace_outlines(&solid, width);
+        assert_eq!(outlines.len(), 2);
+        assert!(outlines.iter().all(|outline| outline.len() == 4));
+    }
+
+    #[test]
+    fn simplify_staircase() {
+        let (solid, width) = grid(&["#...", "##..", "###.", "####"]);
+        let outlines = trace_outlines(&solid, width);
+        assert_eq!(outlines.len(), 1);
+        assert_eq!(outlines[0].len(), 10);
+
+        assert_eq!(simplify(&outlines[0], 0.0), outlines[0]);
+        // Every step is within a pixel of the diagonal, only the ends of the halves are kept
+        assert_eq!(
+            simplify(&outlines[0], 1.0),
+            vec![IVec2::new(0, 0), IVec2::new(4, 0), IVec2::new(1, 4)]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Deleting tiles once enough of their pixels are removed, like when mining or eroding them.
+//!
+//! Add [`Destructible`] to a tile entity to opt in.
+//! Destructible tiles carved down to nothing are deleted here as well, so they always send a [`TileDestroyedEvent`].
+#![allow(clippy::type_complexity)]
+use bevy::prelude::*;
+
+use crate::{
+    chunk::{Chunk, ChunkLocation},
+    events::{TileDeletedEvent, TileDestroyedEvent},
+    tile::{ChunkTileLocation, Tile},
+    tilemap::Tilemap,
+};
+
+/// Deletes the tile once the fraction of its visible pixels left drops to `threshold`
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq)]
+#[reflect(Component)]
+pub struct Destructible {
+    /// The tile is destroyed once `remaining / initial` is at or below this
+    pub threshold: f32,
+    /// The visible pixels when the tile was first counted
+    initial: usize,
+    remaining: usize,
+}
+
+impl Destructible {
+    /// Destroys the tile once all of its pixels are removed
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Destroys the tile once the fraction of its pixels left is at or below `threshold`
+    pub fn with_threshold(threshold: f32) -> Self {
+        Self {
+            threshold,
+            ..Default::default()
+        }
+    }
+
+    /// The visible pixels the tile had when it was first counted
+    pub fn initial(&self) -> usize {
+        self.initial
+    }
+
+    /// The visible pixels the tile has left
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// The fraction of the tile's pixels left, from 0 to 1
+    pub fn health(&self) -> f32 {
+        if self.initial == 0 {
+            return 0.0;
+        }
+
+        self.remaining as f32 / self.initial as f32
+    }
+
+    /// Tiles that never had visible pixels aren't destroyed
+    fn is_destroyed(&self) -> bool {
+        self.initial > 0 && (self.remaining == 0 || self.health() <= self.threshold)
+    }
+}
+
+/// Recounts the pixels of changed destructible tiles, deleting the ones that are used up
+pub fn tile_destruction(
+    mut commands: Commands,
+    mut tiles: Query<
+        (
+            Entity,
+            &Parent,
+            &ChunkTileLocation,
+            &Tile,
+            &mut Destructible,
+        ),
+        Or<(
+            Changed<Tile>,
+            Added<Destructible>,
+            Changed<ChunkTileLocation>,
+        )>,
+    >,
+    mut chunks: Query<(&mut Chunk, &ChunkLocation, &Parent)>,
+    tilemaps: Query<&Tilemap>,
+    mut tile_deleted: EventWriter<TileDeletedEvent>,
+    mut tile_destroyed: EventWriter<TileDestroyedEvent>,
+) {
+    for (entity, parent, location, tile, mut destructible) in &mut tiles {
+        destructible.remaining = tile.pixel_count();
+        if destructible.initial == 0 {
+            destructible.initial = destructible.remaining;
+        }
+
+        if !destructible.is_destroyed() {
+            continue;
+        }
+
+        let chunk_entity = parent.get();
+        let Ok((mut chunk, chunk_loc, tilemap_entity)) = chunks.get_mut(chunk_entity) else {
+            continue;
+        };
+        if chunk.get_tile(location.0) != Some(entity) {
+            continue;
+        }
+        let Ok(tilemap) = tilemaps.get(tilemap_entity.get()) else {
+            continue;
+        };
+
+        let loc = chunk_loc.0.tile(location.0, tilemap.dimensions());
+        chunk.delete_tile(location.0, &mut commands);
+
+        tile_deleted.send(TileDeletedEvent {
+            tilemap: tilemap_entity.get(),
+            loc,
+            chunk: chunk_entity,
+            tile: entity,
+        });
+        tile_destroyed.send(TileDestroyedEvent {
+            tilemap: tilemap_entity.get(),
+            loc,
+            chunk: chunk_entity,
+            tile: entity,
+        });
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{CHUNK_SIZE, TILE_SIZE};
+
+/// The size of the chunks and tiles that make up a single tilemap.
+/// Both sizes are always greater than zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TilemapDimensions {
+    chunk_size: usize,
+    tile_size: usize,
+}
+
+impl Default for TilemapDimensions {
+    fn default() -> Self {
+        Self {
+            chunk_size: CHUNK_SIZE,
+            tile_size: TILE_SIZE,
+        }
+    }
+}
+
+impl TilemapDimensions {
+    pub const fn new(chunk_size: usize, tile_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must be greater than zero");
+        assert!(tile_size > 0, "tile size must be greater than zero");
+
+        Self {
+            chunk_size,
+            tile_size,
+        }
+    }
+
+    /// The width and height of a chunk, in tiles.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// The width and height of a tile, in pixels.
+    pub fn tile_size(&self) -> usize {
+        self.tile_size
+    }
+
+    /// The width and height of a chunk's texture, in pixels.
+    pub fn chunk_pixels(&self) -> usize {
+        self.chunk_size * self.tile_size
+    }
+}
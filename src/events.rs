@@ -0,0 +1,79 @@
+//! Events sent by [`tilemap_event_system`](crate::tilemap::tilemap_event_system)
+//! after a queued tilemap change has been applied.
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::{Color, Entity, Event, EventWriter},
+};
+
+use crate::position::{ChunkPos, PixelPos, TilePos};
+
+/// Sent when a tilemap creates a new chunk
+#[derive(Event, Clone, Debug)]
+pub struct ChunkCreatedEvent {
+    pub tilemap: Entity,
+    pub loc: ChunkPos,
+    pub chunk: Entity,
+}
+
+/// Sent when [chunk streaming](crate::streaming) unloads a chunk
+#[derive(Event, Clone, Debug)]
+pub struct ChunkUnloadedEvent {
+    pub tilemap: Entity,
+    pub loc: ChunkPos,
+}
+
+/// Sent when [chunk streaming](crate::streaming) loads a previously unloaded chunk from its store
+#[derive(Event, Clone, Debug)]
+pub struct ChunkLoadedEvent {
+    pub tilemap: Entity,
+    pub loc: ChunkPos,
+    pub chunk: Entity,
+}
+
+/// Sent when a tile is placed in a chunk
+#[derive(Event, Clone, Debug)]
+pub struct TileSetEvent {
+    pub tilemap: Entity,
+    pub loc: TilePos,
+    pub chunk: Entity,
+    pub tile: Entity,
+}
+
+/// Sent when a tile is removed from a chunk, either by deleting it or by placing another tile over it
+#[derive(Event, Clone, Debug)]
+pub struct TileDeletedEvent {
+    pub tilemap: Entity,
+    pub loc: TilePos,
+    pub chunk: Entity,
+    pub tile: Entity,
+}
+
+/// Sent when a [`Destructible`](crate::destruction::Destructible) tile is deleted for running out of pixels.
+/// A [`TileDeletedEvent`] is sent as well.
+#[derive(Event, Clone, Debug)]
+pub struct TileDestroyedEvent {
+    pub tilemap: Entity,
+    pub loc: TilePos,
+    pub chunk: Entity,
+    pub tile: Entity,
+}
+
+/// Sent when a pixel of a tile is changed through [`Tilemap::set_pixel`](crate::tilemap::Tilemap::set_pixel)
+#[derive(Event, Clone, Debug)]
+pub struct PixelChangedEvent {
+    pub tilemap: Entity,
+    pub loc: TilePos,
+    pub pixel: PixelPos,
+    pub color: Color,
+    pub chunk: Entity,
+    pub tile: Entity,
+}
+
+/// Writers for every tilemap event
+#[derive(SystemParam)]
+pub struct TilemapEventWriters<'w> {
+    pub(crate) chunk_created: EventWriter<'w, ChunkCreatedEvent>,
+    pub(crate) tile_set: EventWriter<'w, TileSetEvent>,
+    pub(crate) tile_deleted: EventWriter<'w, TileDeletedEvent>,
+    pub(crate) pixel_changed: EventWriter<'w, PixelChangedEvent>,
+}
@@ -0,0 +1,182 @@
+//! Filling new chunks procedurally.
+//!
+//! Add a [`TilemapGenerator`] to a tilemap entity, and every chunk the tilemap creates
+//! is generated on the [`AsyncComputeTaskPool`] and placed once the task finishes.
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, AsyncComputeTaskPool, Task},
+};
+
+use crate::{
+    chunk::Chunk,
+    dimensions::TilemapDimensions,
+    events::ChunkCreatedEvent,
+    position::{ChunkPos, LocalTilePos},
+    tile::Tile,
+    tilemap::Tilemap,
+};
+
+pub trait ChunkGenerator: Send + Sync + 'static {
+    /// Generates the tiles of the chunk at `chunk`.
+    /// Runs on the [`AsyncComputeTaskPool`], so it shouldn't rely on the current frame.
+    fn generate(&self, chunk: ChunkPos, seed: u64, dimensions: TilemapDimensions)
+        -> GeneratedChunk;
+}
+
+/// The generator used for new chunks of a tilemap
+#[derive(Component, Clone)]
+pub struct TilemapGenerator {
+    pub generator: Arc<dyn ChunkGenerator>,
+    pub seed: u64,
+}
+
+impl TilemapGenerator {
+    pub fn new(generator: impl ChunkGenerator, seed: u64) -> Self {
+        Self {
+            generator: Arc::new(generator),
+            seed,
+        }
+    }
+}
+
+/// The tiles a [`ChunkGenerator`] made for a chunk
+#[derive(Clone, Debug)]
+pub struct GeneratedChunk {
+    dimensions: TilemapDimensions,
+    tiles: Vec<Option<Tile>>,
+}
+
+impl GeneratedChunk {
+    pub fn new(dimensions: TilemapDimensions) -> Self {
+        Self {
+            dimensions,
+            tiles: vec![None; dimensions.chunk_size() * dimensions.chunk_size()],
+        }
+    }
+
+    pub fn dimensions(&self) -> TilemapDimensions {
+        self.dimensions
+    }
+
+    /// Sets the tile at a location inside of the chunk.
+    /// Tiles that aren't the tilemap's tile size are skipped with a warning.
+    pub fn set_tile(&mut self, loc: LocalTilePos, tile: Tile) {
+        if tile.size() != self.dimensions.tile_size() {
+            warn!(
+                "Skipped generating a {} pixel tile in a tilemap with {} pixel tiles",
+                tile.size(),
+                self.dimensions.tile_size()
+            );
+            return;
+        }
+
+        if let Some(index) = self.tile_index(loc) {
+            self.tiles[index] = Some(tile);
+        }
+    }
+
+    pub fn get_tile(&self, loc: LocalTilePos) -> Option<&Tile> {
+        self.tiles[self.tile_index(loc)?].as_ref()
+    }
+
+    pub fn get_tile_mut(&mut self, loc: LocalTilePos) -> Option<&mut Tile> {
+        let index = self.tile_index(loc)?;
+        self.tiles[index].as_mut()
+    }
+
+    pub fn delete_tile(&mut self, loc: LocalTilePos) {
+        if let Some(index) = self.tile_index(loc) {
+            self.tiles[index] = None;
+        }
+    }
+
+    /// Iterates over every generated tile and its location inside of the chunk
+    pub fn tiles(self) -> impl Iterator<Item = (LocalTilePos, Tile)> {
+        let chunk_size = self.dimensions.chunk_size();
+        self.tiles
+            .into_iter()
+            .enumerate()
+            .filter_map(move |(index, tile)| {
+                let loc =
+                    LocalTilePos::new((index / chunk_size) as i32, (index % chunk_size) as i32);
+                tile.map(|tile| (loc, tile))
+            })
+    }
+
+    fn tile_index(&self, loc: LocalTilePos) -> Option<usize> {
+        if !loc.in_bounds(self.dimensions) {
+            return None;
+        }
+
+        Some(loc.0.x as usize * self.dimensions.chunk_size() + loc.0.y as usize)
+    }
+}
+
+/// A chunk that is still being generated
+#[derive(Component)]
+pub struct GeneratingChunk {
+    loc: ChunkPos,
+    task: Task<GeneratedChunk>,
+}
+
+pub fn chunk_generation_start(
+    mut commands: Commands,
+    mut chunk_created_events: EventReader<ChunkCreatedEvent>,
+    tilemaps: Query<(&Tilemap, &TilemapGenerator)>,
+) {
+    let pool = AsyncComputeTaskPool::get();
+
+    for event in chunk_created_events.read() {
+        let Ok((tilemap, generator)) = tilemaps.get(event.tilemap) else {
+            continue;
+        };
+
+        let (loc, seed, dimensions) = (event.loc, generator.seed, tilemap.dimensions());
+        let generator = generator.generator.clone();
+        let task = pool.spawn(async move { generator.generate(loc, seed, dimensions) });
+
+        commands
+            .entity(event.chunk)
+            .insert(GeneratingChunk { loc, task });
+    }
+}
+
+/// Places the tiles of finished generation tasks.
+/// Tiles that were placed while the chunk was generating are kept.
+pub fn chunk_generation_finish(
+    mut commands: Commands,
+    mut generating: Query<(Entity, &Parent, &mut GeneratingChunk)>,
+    mut tilemaps: Query<&mut Tilemap>,
+    chunks: Query<&Chunk>,
+) {
+    for (entity, parent, mut generating) in &mut generating {
+        if !generating.task.is_finished() {
+            continue;
+        }
+
+        let generated = block_on(&mut generating.task);
+        commands.entity(entity).remove::<GeneratingChunk>();
+
+        let Ok(mut tilemap) = tilemaps.get_mut(parent.get()) else {
+            continue;
+        };
+        if generated.dimensions() != tilemap.dimensions() {
+            error!(
+                "Generated chunk {} doesn't match the tilemap's dimensions",
+                generating.loc
+            );
+            continue;
+        }
+
+        let dimensions = tilemap.dimensions();
+        for (loc, tile) in generated.tiles() {
+            let loc = generating.loc.tile(loc, dimensions);
+            // Occupied locations keep the tile placed while generating
+            tilemap
+                .try_set_tile(&mut commands, &chunks, loc, tile, ())
+                .ok();
+        }
+    }
+}
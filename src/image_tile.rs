@@ -0,0 +1,116 @@
+//! Tiles copied from a region of an image, kept up to date when the image is reloaded.
+//!
+//! Place tiles with [`Tilemap::set_image_tile`](crate::tilemap::Tilemap::set_image_tile). When the image
+//! changes, every tile that still has the pixels it last copied gets the new pixels, tiles whose pixels
+//! were changed since are left alone.
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use bevy::prelude::*;
+
+use crate::tile::Tile;
+
+/// The image region a tile's pixels are copied from
+#[derive(Component, Clone, Debug)]
+pub struct TileImageSource {
+    pub image: Handle<Image>,
+    /// The pixels copied, in image pixels from the top left
+    pub region: URect,
+    /// The tile as it was last copied, sharing its pixels with the placed tile until that is changed
+    copied: Option<Tile>,
+}
+
+impl TileImageSource {
+    pub fn new(image: Handle<Image>, region: URect) -> Self {
+        Self {
+            image,
+            region,
+            copied: None,
+        }
+    }
+
+    /// Copies the region out of the image, a transparent tile if the image isn't loaded yet.
+    /// Returns `None` if the region isn't square, or doesn't fit in the image.
+    pub fn tile(&mut self, images: &Assets<Image>) -> Option<Tile> {
+        if self.region.width() == 0 || self.region.width() != self.region.height() {
+            return None;
+        }
+
+        let tile = match images.get(&self.image) {
+            Some(image) if self.fits(image) => Tile::from_image(image, self.ranges()),
+            Some(_) => return None,
+            None => Tile::from_color(Color::NONE, self.region.width() as usize),
+        };
+        self.copied = Some(tile.clone());
+
+        Some(tile)
+    }
+
+    /// Whether the tile still has the pixels that were last copied into it
+    pub fn is_unmodified(&self, tile: &Tile) -> bool {
+        match &self.copied {
+            Some(copied) => copied.shares_pixels(tile),
+            None => true,
+        }
+    }
+
+    /// Whether the region is square and inside of the image
+    fn fits(&self, image: &Image) -> bool {
+        self.region.width() == self.region.height()
+            && self.region.max.x <= image.size().x
+            && self.region.max.y <= image.size().y
+    }
+
+    fn ranges(&self) -> (Range<usize>, Range<usize>) {
+        (
+            self.region.min.x as usize..self.region.max.x as usize,
+            self.region.min.y as usize..self.region.max.y as usize,
+        )
+    }
+}
+
+/// Copies reloaded images into the unmodified tiles that came from them
+pub fn image_tile_reload(
+    mut image_events: EventReader<AssetEvent<Image>>,
+    images: Res<Assets<Image>>,
+    mut tiles: Query<(&mut Tile, &mut TileImageSource)>,
+) {
+    let changed = image_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    if changed.is_empty() {
+        return;
+    }
+
+    // Tiles from the same region share the new pixels as well
+    let mut copies: HashMap<(AssetId<Image>, UVec2, UVec2), Tile> = HashMap::new();
+    for (mut tile, mut source) in &mut tiles {
+        let id = source.image.id();
+        if !changed.contains(&id) || !source.is_unmodified(&tile) {
+            continue;
+        }
+        let Some(image) = images.get(id) else {
+            continue;
+        };
+        if !source.fits(image) {
+            continue;
+        }
+
+        let copy = copies
+            .entry((id, source.region.min, source.region.max))
+            .or_insert_with(|| Tile::from_image(image, source.ranges()))
+            .clone();
+        if copy.size() != tile.size() {
+            continue;
+        }
+
+        tile.replace(&copy);
+        source.copied = Some(copy);
+    }
+}
@@ -1,15 +1,38 @@
+mod bitset;
+
+pub mod access;
+pub mod animation;
+pub mod autotile;
 pub mod chunk;
+pub mod collider;
+pub mod destruction;
+pub mod dimensions;
+pub mod events;
+pub mod generation;
+pub mod image_tile;
+pub mod material;
 pub mod multi_tile;
+pub mod overlap;
+pub mod position;
+pub mod raycast;
+pub mod simulation;
 pub mod tile;
+pub mod tile_kind;
+pub mod tileset;
 
 pub mod plugin;
 
+pub mod save;
+pub mod streaming;
+
 pub mod util;
 
 pub mod tilemap;
 
 pub mod prelude;
 
+/// The default chunk size, in tiles
 pub const CHUNK_SIZE: usize = 16;
 
+/// The default tile size, in pixels
 pub const TILE_SIZE: usize = 8;
@@ -0,0 +1,210 @@
+//! Per-pixel materials, so gameplay can tell what a pixel is made of without going by its color.
+//!
+//! Materials are registered in the [`MaterialRegistry`] resource, and every pixel of a [`Tile`](crate::tile::Tile)
+//! stores the [`MaterialId`] it was made from. Pixels set by color alone have [`MaterialId::NONE`].
+use std::ops::{BitOr, BitOrAssign};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::position::WorldPixelPos;
+
+/// Identifies a material in the [`MaterialRegistry`]
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct MaterialId(pub u16);
+
+impl MaterialId {
+    /// Pixels that aren't made of any material
+    pub const NONE: Self = Self(0);
+
+    pub fn is_none(self) -> bool {
+        self == Self::NONE
+    }
+}
+
+/// What a material does, combined with `|`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MaterialFlags(u32);
+
+impl MaterialFlags {
+    pub const NONE: Self = Self(0);
+    /// Blocks movement, and is part of chunk colliders
+    pub const SOLID: Self = Self(1 << 0);
+    /// Can be mined or carved away
+    pub const DIGGABLE: Self = Self(1 << 1);
+    /// Falls and piles up in the falling sand simulation
+    pub const POWDER: Self = Self(1 << 2);
+    /// Falls and flows sideways in the falling sand simulation
+    pub const LIQUID: Self = Self(1 << 3);
+    /// Rises and spreads sideways in the falling sand simulation
+    pub const GAS: Self = Self(1 << 4);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for MaterialFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for MaterialFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    /// The color pixels of the material are derived from
+    pub color: Color,
+    /// How far the brightness of each pixel may stray from `color`, from 0 to 1
+    pub color_variance: f32,
+    /// Denser materials sink through lighter powders, liquids and gases
+    pub density: f32,
+    pub flags: MaterialFlags,
+}
+
+impl Material {
+    /// A solid material with a single color
+    pub fn new(name: impl Into<String>, color: Color) -> Self {
+        Self {
+            name: name.into(),
+            color,
+            color_variance: 0.0,
+            density: 1.0,
+            flags: MaterialFlags::SOLID,
+        }
+    }
+
+    pub fn with_color_variance(mut self, color_variance: f32) -> Self {
+        self.color_variance = color_variance;
+        self
+    }
+
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.density = density;
+        self
+    }
+
+    pub fn with_flags(mut self, flags: MaterialFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.flags.contains(MaterialFlags::SOLID)
+    }
+
+    /// The color of a pixel of this material.
+    /// The variance is seeded by the pixel's position, so the same pixel always gets the same color.
+    pub fn pixel_color(&self, world_pixel: WorldPixelPos) -> Color {
+        if self.color_variance <= 0.0 {
+            return self.color;
+        }
+
+        let shift = pixel_noise(world_pixel) * self.color_variance;
+        let [r, g, b, a] = self.color.as_rgba_f32();
+        Color::rgba(
+            (r + shift).clamp(0.0, 1.0),
+            (g + shift).clamp(0.0, 1.0),
+            (b + shift).clamp(0.0, 1.0),
+            a,
+        )
+    }
+}
+
+/// A hash of the pixel's position, from -1 to 1
+fn pixel_noise(world_pixel: WorldPixelPos) -> f32 {
+    let mut hash = (world_pixel.0.x as u32).wrapping_mul(0x8da6_b343)
+        ^ (world_pixel.0.y as u32).wrapping_mul(0xd816_3841);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 16;
+
+    hash as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+/// Every material pixels can be made of
+#[derive(Resource, Clone, Debug, Default)]
+pub struct MaterialRegistry {
+    /// The material with id `n` is at `n - 1`, id 0 is [`MaterialId::NONE`]
+    materials: Vec<Material>,
+}
+
+impl MaterialRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a material, returning its id.
+    /// Ids are handed out in order, so registering the same materials in the same order gives the same ids.
+    pub fn register(&mut self, material: Material) -> MaterialId {
+        assert!(
+            self.materials.len() < u16::MAX as usize,
+            "Too many materials registered"
+        );
+
+        self.materials.push(material);
+        MaterialId(self.materials.len() as u16)
+    }
+
+    pub fn get(&self, id: MaterialId) -> Option<&Material> {
+        self.materials.get((id.0 as usize).checked_sub(1)?)
+    }
+
+    pub fn get_mut(&mut self, id: MaterialId) -> Option<&mut Material> {
+        self.materials.get_mut((id.0 as usize).checked_sub(1)?)
+    }
+
+    /// Finds a material by name
+    pub fn find(&self, name: &str) -> Option<MaterialId> {
+        self.materials
+            .iter()
+            .position(|material| material.name == name)
+            .map(|index| MaterialId(index as u16 + 1))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (MaterialId, &Material)> {
+        self.materials
+            .iter()
+            .enumerate()
+            .map(|(index, material)| (MaterialId(index as u16 + 1), material))
+    }
+
+    /// The color of a pixel of the material, transparent for unknown materials
+    pub fn pixel_color(&self, id: MaterialId, world_pixel: WorldPixelPos) -> Color {
+        self.get(id)
+            .map(|material| material.pixel_color(world_pixel))
+            .unwrap_or(Color::NONE)
+    }
+
+    /// Whether a pixel blocks colliders, raycasts and overlap queries.
+    /// Pixels made of a registered material are solid when the material is [`MaterialFlags::SOLID`],
+    /// other pixels when their alpha is above `alpha_threshold`.
+    pub fn is_solid(&self, color: Color, material: MaterialId, alpha_threshold: f32) -> bool {
+        match self.get(material) {
+            Some(material) => material.is_solid(),
+            None => color.a() > alpha_threshold,
+        }
+    }
+
+    /// The flags of the material, no flags for unknown materials
+    pub fn flags(&self, id: MaterialId) -> MaterialFlags {
+        self.get(id)
+            .map(|material| material.flags)
+            .unwrap_or_default()
+    }
+}
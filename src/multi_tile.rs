@@ -2,22 +2,22 @@ use bevy::prelude::*;
 
 use crate::{
     chunk::Chunk,
+    position::TilePos,
     tile::{DeletingTile, Tile},
     tilemap::Tilemap,
-    TILE_SIZE,
 };
 
 #[derive(Component, Clone, Debug)]
 pub struct MultiTileMarker {
-    entity: Entity,
+    pub(crate) entity: Entity,
 }
 
 #[derive(Component, Clone, Debug)]
 pub struct MultiTile {
-    pos: IVec2,
-    size: IVec2,
-    pixels: Vec<Vec<Color>>,
-    entities: Vec<Entity>,
+    pub(crate) pos: TilePos,
+    pub(crate) size: IVec2,
+    pub(crate) pixels: Vec<Vec<Color>>,
+    pub(crate) entities: Vec<Entity>,
 }
 
 impl MultiTile {
@@ -26,7 +26,7 @@ impl MultiTile {
 
         Self {
             pixels: data,
-            pos: IVec2::new(0, 0),
+            pos: TilePos::ZERO,
             size: IVec2::new(0, 0),
             entities: vec![],
         }
@@ -50,53 +50,62 @@ impl MultiTile {
             pixels.push(row)
         }
 
-        let size = IVec2::new(
-            pixels.len() as i32 / TILE_SIZE as i32,
-            pixels[0].len() as i32 / TILE_SIZE as i32,
-        );
-
         Self {
             pixels,
-            pos: IVec2::new(0, 0),
-            size,
+            pos: TilePos::ZERO,
+            size: IVec2::new(0, 0),
             entities: vec![],
         }
     }
 
-    #[allow(clippy::needless_range_loop)]
-    pub fn get_tile(&self, offset: IVec2) -> Option<Tile> {
-        if offset.x < 0 || offset.x > self.size.x || offset.y < 0 || offset.y > self.size.y {
+    /// The size of the multi tile in tiles, when split into tiles `tile_size` pixels wide
+    pub fn tile_count(&self, tile_size: usize) -> IVec2 {
+        let height = self.pixels.len();
+        let width = self.pixels.first().map(|row| row.len()).unwrap_or(0);
+
+        IVec2::new((width / tile_size) as i32, (height / tile_size) as i32)
+    }
+
+    pub fn get_tile(&self, offset: IVec2, tile_size: usize) -> Option<Tile> {
+        let size = self.tile_count(tile_size);
+        if offset.x < 0 || offset.x >= size.x || offset.y < 0 || offset.y >= size.y {
             return None;
         }
 
-        let mut pixels = [[Color::NONE; TILE_SIZE]; TILE_SIZE];
+        let mut pixels = Vec::with_capacity(tile_size * tile_size);
 
-        for y in 0..TILE_SIZE {
-            for x in 0..TILE_SIZE {
-                pixels[y][x] = self.pixels[y + offset.y as usize * TILE_SIZE]
-                    [x + offset.x as usize * TILE_SIZE];
+        for y in 0..tile_size {
+            for x in 0..tile_size {
+                pixels.push(
+                    self.pixels[y + offset.y as usize * tile_size]
+                        [x + offset.x as usize * tile_size],
+                );
             }
         }
 
-        Some(Tile::from_pixels(pixels))
+        Some(Tile::from_pixel_vec(tile_size, pixels))
     }
 
-    pub fn place(mut self, loc: IVec2, tilemap: &mut Tilemap, commands: &mut Commands) -> Entity {
-        self.pos.x = loc.x;
-        self.pos.y = loc.y;
+    pub fn place(mut self, loc: TilePos, tilemap: &mut Tilemap, commands: &mut Commands) -> Entity {
+        let tile_size = tilemap.dimensions().tile_size();
+
+        self.pos = loc;
+        self.size = self.tile_count(tile_size);
 
         let mut my_entity = commands.spawn_empty();
         let entity_id = my_entity.id();
         // Create the tiles
         for tile_x in 0..self.size.x {
             for tile_y in 0..self.size.y {
-                let entity = tilemap.set_tile(
-                    commands,
-                    IVec2::new(loc.x + tile_x, loc.y + tile_y),
-                    self.get_tile(IVec2::new(tile_x, self.size.y - 1 - tile_y))
-                        .unwrap(),
-                    MultiTileMarker { entity: entity_id },
-                );
+                let entity = tilemap
+                    .set_tile(
+                        commands,
+                        loc + IVec2::new(tile_x, tile_y),
+                        self.get_tile(IVec2::new(tile_x, self.size.y - 1 - tile_y), tile_size)
+                            .unwrap(),
+                        MultiTileMarker { entity: entity_id },
+                    )
+                    .expect("Tiles are cut to the tilemap's tile size");
                 self.entities.push(entity)
             }
         }
@@ -130,7 +139,7 @@ pub fn multi_tile_delete(
                 if let Ok(mut tilemap) = tilemaps.get_mut(tilemap.get()) {
                     for x in 0..multi_tile.size.x {
                         for y in 0..multi_tile.size.y {
-                            let loc = IVec2::new(x + multi_tile.pos.x, y + multi_tile.pos.y);
+                            let loc = multi_tile.pos + IVec2::new(x, y);
                             if let Some(tile_entity) = tilemap.get_tile(loc, &chunk_data) {
                                 if multi_tile.entities.contains(&tile_entity) {
                                     tilemap.delete_tile(loc);
@@ -0,0 +1,395 @@
+//! Pixel perfect overlap and sweep queries against a tilemap.
+//!
+//! Shapes are sets of world pixels. Every query takes the alpha threshold above which pixels without a material count.
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    chunk::Chunk,
+    material::MaterialRegistry,
+    position::{TilePos, WorldPixelPos},
+    tile::Tile,
+    tilemap::Tilemap,
+};
+
+/// A set of world pixels to test against the tilemap
+#[derive(Clone, Debug, PartialEq)]
+pub enum PixelShape {
+    /// The pixels from `min` up to, but not including, `min + size`
+    Rect { min: WorldPixelPos, size: UVec2 },
+    /// The pixels whose centers are at most `radius` pixels from the center of `center`
+    Circle { center: WorldPixelPos, radius: u32 },
+    /// The set pixels of the mask, with the mask's bottom left pixel at `min`
+    Mask { min: WorldPixelPos, mask: PixelMask },
+    /// The pixels whose centers are inside of the polygon, the points are in world pixel space
+    Polygon { points: Vec<Vec2> },
+}
+
+impl PixelShape {
+    pub fn rect(min: WorldPixelPos, size: UVec2) -> Self {
+        Self::Rect { min, size }
+    }
+
+    pub fn circle(center: WorldPixelPos, radius: u32) -> Self {
+        Self::Circle { center, radius }
+    }
+
+    pub fn mask(min: WorldPixelPos, mask: PixelMask) -> Self {
+        Self::Mask { min, mask }
+    }
+
+    pub fn polygon(points: Vec<Vec2>) -> Self {
+        Self::Polygon { points }
+    }
+
+    /// The world pixels covered by the shape
+    pub fn pixels(&self) -> Vec<WorldPixelPos> {
+        match self {
+            PixelShape::Rect { min, size } => (0..size.y as i32)
+                .flat_map(|y| (0..size.x as i32).map(move |x| *min + IVec2::new(x, y)))
+                .collect(),
+            PixelShape::Circle { center, radius } => {
+                let radius = *radius as i32;
+                (-radius..=radius)
+                    .flat_map(|y| (-radius..=radius).map(move |x| IVec2::new(x, y)))
+                    .filter(|offset| offset.length_squared() <= radius * radius)
+                    .map(|offset| *center + offset)
+                    .collect()
+            }
+            PixelShape::Mask { min, mask } => mask.pixels().map(|pixel| *min + pixel).collect(),
+            PixelShape::Polygon { points } => {
+                if points.len() < 3 {
+                    return vec![];
+                }
+
+                let min = points
+                    .iter()
+                    .copied()
+                    .reduce(Vec2::min)
+                    .expect("Polygon has points")
+                    .floor();
+                let max = points
+                    .iter()
+                    .copied()
+                    .reduce(Vec2::max)
+                    .expect("Polygon has points")
+                    .ceil();
+                (min.y as i32..max.y as i32)
+                    .flat_map(|y| (min.x as i32..max.x as i32).map(move |x| IVec2::new(x, y)))
+                    .filter(|pixel| contains_point(points, pixel.as_vec2() + 0.5))
+                    .map(WorldPixelPos)
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Even-odd test for whether the point is inside of the polygon
+fn contains_point(points: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    let mut previous = points[points.len() - 1];
+    for current in points {
+        if (current.y > point.y) != (previous.y > point.y) {
+            let x = (previous.x - current.x) * (point.y - current.y) / (previous.y - current.y)
+                + current.x;
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+        previous = *current;
+    }
+
+    inside
+}
+
+/// Which pixels of a sprite are solid, for [`PixelShape::Mask`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PixelMask {
+    width: usize,
+    height: usize,
+    /// Row major with y going up, `set[y * width + x]`
+    set: Vec<bool>,
+}
+
+impl PixelMask {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            set: vec![false; width * height],
+        }
+    }
+
+    /// Creates a mask of the image's pixels with an alpha above `alpha_threshold`
+    pub fn from_image(image: &Image, alpha_threshold: f32) -> Self {
+        let (width, height) = (image.size().x as usize, image.size().y as usize);
+        let mut mask = Self::new(width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let alpha = image.data[(y * width + x) * 4 + 3] as f32 / 255.0;
+                // Images are stored top to bottom
+                mask.set(
+                    IVec2::new(x as i32, (height - 1 - y) as i32),
+                    alpha > alpha_threshold,
+                );
+            }
+        }
+
+        mask
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Sets a pixel of the mask, `(0, 0)` is the bottom left pixel
+    pub fn set(&mut self, pixel: IVec2, set: bool) {
+        if let Some(index) = self.index(pixel) {
+            self.set[index] = set;
+        }
+    }
+
+    pub fn get(&self, pixel: IVec2) -> bool {
+        self.index(pixel).is_some_and(|index| self.set[index])
+    }
+
+    /// Iterates over the set pixels
+    pub fn pixels(&self) -> impl Iterator<Item = IVec2> + '_ {
+        let width = self.width;
+        self.set
+            .iter()
+            .enumerate()
+            .filter(|(_, set)| **set)
+            .map(move |(index, _)| IVec2::new((index % width) as i32, (index / width) as i32))
+    }
+
+    fn index(&self, pixel: IVec2) -> Option<usize> {
+        if pixel.x < 0 || pixel.y < 0 {
+            return None;
+        }
+        let (x, y) = (pixel.x as usize, pixel.y as usize);
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        Some(y * self.width + x)
+    }
+}
+
+/// A solid pixel touched by a shape
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelOverlap {
+    pub world_pixel: WorldPixelPos,
+    pub tile: Entity,
+}
+
+/// Where a swept shape was stopped
+#[derive(Clone, Debug, PartialEq)]
+pub struct PixelSweepHit {
+    /// How far the shape can move before touching a solid pixel
+    pub offset: IVec2,
+    /// The solid pixels the shape would overlap one step further
+    pub overlaps: Vec<PixelOverlap>,
+}
+
+/// Finds solid pixels, remembering the tiles looked up so far
+struct SolidPixels<'a> {
+    tilemap: &'a Tilemap,
+    alpha_threshold: f32,
+    materials: &'a MaterialRegistry,
+    cache: HashMap<TilePos, Option<(Entity, &'a Tile)>>,
+}
+
+impl<'a> SolidPixels<'a> {
+    fn new(tilemap: &'a Tilemap, alpha_threshold: f32, materials: &'a MaterialRegistry) -> Self {
+        Self {
+            tilemap,
+            alpha_threshold,
+            materials,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// The tile of the pixel, if the pixel is solid
+    fn get(
+        &mut self,
+        world_pixel: WorldPixelPos,
+        chunks: &Query<&Chunk>,
+        tiles: &'a Query<&Tile>,
+    ) -> Option<Entity> {
+        let (loc, pixel) = world_pixel.split(self.tilemap.dimensions());
+        let tilemap = self.tilemap;
+        let (entity, tile) = (*self.cache.entry(loc).or_insert_with(|| {
+            let entity = tilemap.get_tile(loc, chunks)?;
+            Some((entity, tiles.get(entity).ok()?))
+        }))?;
+
+        tile.is_solid(pixel, self.alpha_threshold, self.materials)
+            .then_some(entity)
+    }
+
+    fn overlaps(
+        &mut self,
+        pixels: &[WorldPixelPos],
+        offset: IVec2,
+        chunks: &Query<&Chunk>,
+        tiles: &'a Query<&Tile>,
+    ) -> Vec<PixelOverlap> {
+        pixels
+            .iter()
+            .filter_map(|pixel| {
+                let world_pixel = *pixel + offset;
+                let tile = self.get(world_pixel, chunks, tiles)?;
+                Some(PixelOverlap { world_pixel, tile })
+            })
+            .collect()
+    }
+}
+
+/// Every solid pixel the shape overlaps
+pub fn overlap(
+    tilemap: &Tilemap,
+    shape: &PixelShape,
+    alpha_threshold: f32,
+    materials: &MaterialRegistry,
+    chunks: &Query<&Chunk>,
+    tiles: &Query<&Tile>,
+) -> Vec<PixelOverlap> {
+    SolidPixels::new(tilemap, alpha_threshold, materials).overlaps(
+        &shape.pixels(),
+        IVec2::ZERO,
+        chunks,
+        tiles,
+    )
+}
+
+/// Whether the shape overlaps any solid pixel
+pub fn overlaps(
+    tilemap: &Tilemap,
+    shape: &PixelShape,
+    alpha_threshold: f32,
+    materials: &MaterialRegistry,
+    chunks: &Query<&Chunk>,
+    tiles: &Query<&Tile>,
+) -> bool {
+    let mut solid = SolidPixels::new(tilemap, alpha_threshold, materials);
+    shape
+        .pixels()
+        .into_iter()
+        .any(|pixel| solid.get(pixel, chunks, tiles).is_some())
+}
+
+/// Moves the shape along `motion` one pixel at a time, stopping before the first step that overlaps a solid pixel.
+/// Returns `None` if the shape can move the whole way, and an offset of zero if the shape already overlaps.
+pub fn sweep(
+    tilemap: &Tilemap,
+    shape: &PixelShape,
+    motion: IVec2,
+    alpha_threshold: f32,
+    materials: &MaterialRegistry,
+    chunks: &Query<&Chunk>,
+    tiles: &Query<&Tile>,
+) -> Option<PixelSweepHit> {
+    let mut solid = SolidPixels::new(tilemap, alpha_threshold, materials);
+    let pixels = shape.pixels();
+
+    let steps = motion.abs().max_element();
+    let mut offset = IVec2::ZERO;
+    for step in 0..=steps {
+        let next = if steps == 0 {
+            IVec2::ZERO
+        } else {
+            (motion.as_vec2() * step as f32 / steps as f32)
+                .round()
+                .as_ivec2()
+        };
+
+        let overlaps = solid.overlaps(&pixels, next, chunks, tiles);
+        if !overlaps.is_empty() {
+            return Some(PixelSweepHit { offset, overlaps });
+        }
+        offset = next;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut pixels: Vec<WorldPixelPos>) -> Vec<IVec2> {
+        pixels.sort_by_key(|pixel| (pixel.0.y, pixel.0.x));
+        pixels.into_iter().map(|pixel| pixel.0).collect()
+    }
+
+    #[test]
+    fn rect_pixels() {
+        let shape = PixelShape::rect(WorldPixelPos::new(-1, 2), UVec2::new(2, 1));
+        assert_eq!(
+            sorted(shape.pixels()),
+            vec![IVec2::new(-1, 2), IVec2::new(0, 2)]
+        );
+        assert!(PixelShape::rect(WorldPixelPos::ZERO, UVec2::new(0, 3))
+            .pixels()
+            .is_empty());
+    }
+
+    #[test]
+    fn circle_pixels() {
+        let shape = PixelShape::circle(WorldPixelPos::new(5, 5), 1);
+        assert_eq!(
+            sorted(shape.pixels()),
+            vec![
+                IVec2::new(5, 4),
+                IVec2::new(4, 5),
+                IVec2::new(5, 5),
+                IVec2::new(6, 5),
+                IVec2::new(5, 6),
+            ]
+        );
+        assert_eq!(PixelShape::circle(WorldPixelPos::ZERO, 0).pixels().len(), 1);
+    }
+
+    #[test]
+    fn mask_pixels() {
+        let mut mask = PixelMask::new(2, 2);
+        mask.set(IVec2::new(1, 0), true);
+        mask.set(IVec2::new(0, 1), true);
+        let shape = PixelShape::mask(WorldPixelPos::new(10, 20), mask);
+        assert_eq!(
+            sorted(shape.pixels()),
+            vec![IVec2::new(11, 20), IVec2::new(10, 21)]
+        );
+    }
+
+    #[test]
+    fn polygon_pixels() {
+        // A triangle covering the centers of the pixels on and below the diagonal
+        let shape = PixelShape::polygon(vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(3.0, 3.0),
+        ]);
+        assert_eq!(
+            sorted(shape.pixels()),
+            vec![
+                IVec2::new(0, 0),
+                IVec2::new(1, 0),
+                IVec2::new(2, 0),
+                IVec2::new(1, 1),
+                IVec2::new(2, 1),
+                IVec2::new(2, 2),
+            ]
+        );
+        assert!(PixelShape::polygon(vec![Vec2::ZERO, Vec2::ONE])
+            .pixels()
+            .is_empty());
+    }
+}
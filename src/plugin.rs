@@ -1,22 +1,65 @@
 use bevy::prelude::*;
 
 use crate::{
-    chunk::{chunk_deleter, chunk_texture_update},
+    animation::{tile_animation, TileAnimations},
+    autotile::{autotile_update, AutoTile, AutoTileQueue, AutoTileSets},
+    chunk::{chunk_deleter, chunk_texture_update, chunk_tile_change_detection},
+    collider::chunk_collider_update,
+    destruction::{tile_destruction, Destructible},
+    events::{
+        ChunkCreatedEvent, ChunkLoadedEvent, ChunkUnloadedEvent, PixelChangedEvent,
+        TileDeletedEvent, TileDestroyedEvent, TileSetEvent,
+    },
+    generation::{chunk_generation_finish, chunk_generation_start},
+    image_tile::image_tile_reload,
+    material::MaterialRegistry,
     multi_tile::multi_tile_delete,
+    streaming::chunk_streaming_system,
+    tile_kind::{TileKind, TileRegistry},
     tilemap::tilemap_event_system,
+    tileset::{Tileset, TilesetLoader},
 };
 
 pub struct PixelPlugin;
 
 impl Plugin for PixelPlugin {
     fn build(&self, app: &mut App) {
+        app.add_event::<ChunkCreatedEvent>()
+            .add_event::<TileSetEvent>()
+            .add_event::<TileDeletedEvent>()
+            .add_event::<PixelChangedEvent>()
+            .add_event::<ChunkLoadedEvent>()
+            .add_event::<ChunkUnloadedEvent>()
+            .add_event::<TileDestroyedEvent>();
+
+        app.init_asset::<Tileset>()
+            .init_asset_loader::<TilesetLoader>();
+
+        app.init_resource::<MaterialRegistry>()
+            .init_resource::<TileRegistry>()
+            .init_resource::<TileAnimations>()
+            .init_resource::<AutoTileSets>()
+            .init_resource::<AutoTileQueue>()
+            .register_type::<Destructible>()
+            .register_type::<TileKind>()
+            .register_type::<AutoTile>();
+
         app.add_systems(
             PostUpdate,
             (
+                chunk_streaming_system,
                 chunk_deleter,
                 multi_tile_delete,
-                chunk_texture_update,
+                chunk_generation_finish,
                 tilemap_event_system,
+                chunk_generation_start,
+                autotile_update,
+                image_tile_reload,
+                tile_animation,
+                chunk_tile_change_detection,
+                tile_destruction,
+                chunk_texture_update,
+                chunk_collider_update,
             )
                 .chain(),
         );
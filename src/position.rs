@@ -0,0 +1,222 @@
+//! Typed positions, so chunk, tile and pixel coordinates can't be mixed up.
+//!
+//! Converting between them needs the [`TilemapDimensions`] of the tilemap they belong to.
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
+
+use bevy::prelude::IVec2;
+use serde::{Deserialize, Serialize};
+
+use crate::dimensions::TilemapDimensions;
+
+macro_rules! position {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(pub IVec2);
+
+        impl $name {
+            pub const ZERO: Self = Self(IVec2::ZERO);
+
+            pub const fn new(x: i32, y: i32) -> Self {
+                Self(IVec2::new(x, y))
+            }
+        }
+
+        impl Add<IVec2> for $name {
+            type Output = Self;
+
+            fn add(self, offset: IVec2) -> Self {
+                Self(self.0 + offset)
+            }
+        }
+
+        impl AddAssign<IVec2> for $name {
+            fn add_assign(&mut self, offset: IVec2) {
+                self.0 += offset;
+            }
+        }
+
+        impl Sub<IVec2> for $name {
+            type Output = Self;
+
+            fn sub(self, offset: IVec2) -> Self {
+                Self(self.0 - offset)
+            }
+        }
+
+        impl SubAssign<IVec2> for $name {
+            fn sub_assign(&mut self, offset: IVec2) {
+                self.0 -= offset;
+            }
+        }
+
+        /// The offset between two positions
+        impl Sub for $name {
+            type Output = IVec2;
+
+            fn sub(self, other: Self) -> IVec2 {
+                self.0 - other.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "({}, {})", self.0.x, self.0.y)
+            }
+        }
+    };
+}
+
+position!(
+    /// The location of a chunk, in chunks
+    ChunkPos
+);
+
+position!(
+    /// The location of a tile inside of its chunk, `(0, 0)` is the bottom left tile
+    LocalTilePos
+);
+
+position!(
+    /// The location of a tile in the tilemap, in tiles
+    TilePos
+);
+
+position!(
+    /// The location of a pixel inside of its tile, `(0, 0)` is the top left pixel
+    PixelPos
+);
+
+position!(
+    /// The location of a pixel in the tilemap, going right along x and up along y.
+    /// World pixel `(0, 0)` is the bottom left pixel of tile `(0, 0)`.
+    WorldPixelPos
+);
+
+impl ChunkPos {
+    /// The bottom left tile of the chunk
+    pub fn origin(self, dimensions: TilemapDimensions) -> TilePos {
+        TilePos(self.0 * dimensions.chunk_size() as i32)
+    }
+
+    /// The tilemap location of a tile inside of this chunk
+    pub fn tile(self, local: LocalTilePos, dimensions: TilemapDimensions) -> TilePos {
+        TilePos(self.origin(dimensions).0 + local.0)
+    }
+}
+
+impl LocalTilePos {
+    /// Whether the location is inside of a chunk
+    pub fn in_bounds(self, dimensions: TilemapDimensions) -> bool {
+        let size = dimensions.chunk_size() as i32;
+        self.0.x >= 0 && self.0.x < size && self.0.y >= 0 && self.0.y < size
+    }
+}
+
+impl TilePos {
+    /// The chunk the tile is in
+    pub fn chunk(self, dimensions: TilemapDimensions) -> ChunkPos {
+        ChunkPos(
+            self.0
+                .div_euclid(IVec2::splat(dimensions.chunk_size() as i32)),
+        )
+    }
+
+    /// The location of the tile inside of its chunk
+    pub fn local(self, dimensions: TilemapDimensions) -> LocalTilePos {
+        LocalTilePos(
+            self.0
+                .rem_euclid(IVec2::splat(dimensions.chunk_size() as i32)),
+        )
+    }
+
+    /// The world pixel of a pixel inside of this tile
+    pub fn world_pixel(self, pixel: PixelPos, dimensions: TilemapDimensions) -> WorldPixelPos {
+        let tile_size = dimensions.tile_size() as i32;
+        WorldPixelPos(IVec2::new(
+            self.0.x * tile_size + pixel.0.x,
+            self.0.y * tile_size + tile_size - 1 - pixel.0.y,
+        ))
+    }
+}
+
+impl PixelPos {
+    /// Whether the location is inside of a tile
+    pub fn in_bounds(self, dimensions: TilemapDimensions) -> bool {
+        let size = dimensions.tile_size() as i32;
+        self.0.x >= 0 && self.0.x < size && self.0.y >= 0 && self.0.y < size
+    }
+}
+
+impl WorldPixelPos {
+    /// The tile the pixel is in
+    pub fn tile(self, dimensions: TilemapDimensions) -> TilePos {
+        TilePos(
+            self.0
+                .div_euclid(IVec2::splat(dimensions.tile_size() as i32)),
+        )
+    }
+
+    /// The location of the pixel inside of its tile, the y axis is flipped
+    pub fn pixel(self, dimensions: TilemapDimensions) -> PixelPos {
+        let tile_size = dimensions.tile_size() as i32;
+        let pixel = self.0.rem_euclid(IVec2::splat(tile_size));
+        PixelPos(IVec2::new(pixel.x, tile_size - 1 - pixel.y))
+    }
+
+    /// The tile the pixel is in, and the location of the pixel inside of it
+    pub fn split(self, dimensions: TilemapDimensions) -> (TilePos, PixelPos) {
+        (self.tile(dimensions), self.pixel(dimensions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIMENSIONS: TilemapDimensions = TilemapDimensions::new(16, 8);
+
+    #[test]
+    fn tiles_split_into_chunks() {
+        let loc = TilePos::new(-1, 17);
+        assert_eq!(loc.chunk(DIMENSIONS), ChunkPos::new(-1, 1));
+        assert_eq!(loc.local(DIMENSIONS), LocalTilePos::new(15, 1));
+        assert_eq!(
+            loc.chunk(DIMENSIONS)
+                .tile(loc.local(DIMENSIONS), DIMENSIONS),
+            loc
+        );
+        assert_eq!(
+            ChunkPos::new(2, -1).origin(DIMENSIONS),
+            TilePos::new(32, -16)
+        );
+    }
+
+    #[test]
+    fn pixels_flip_y() {
+        // The top left pixel of tile (0, 0) is the highest world pixel of the tile
+        let world_pixel = TilePos::ZERO.world_pixel(PixelPos::ZERO, DIMENSIONS);
+        assert_eq!(world_pixel, WorldPixelPos::new(0, 7));
+        assert_eq!(
+            WorldPixelPos::new(0, 0).split(DIMENSIONS),
+            (TilePos::ZERO, PixelPos::new(0, 7))
+        );
+        assert_eq!(
+            WorldPixelPos::new(-1, -1).split(DIMENSIONS),
+            (TilePos::new(-1, -1), PixelPos::new(7, 0))
+        );
+
+        for x in -10..10 {
+            for y in -10..10 {
+                let world_pixel = WorldPixelPos::new(x, y);
+                let (tile, pixel) = world_pixel.split(DIMENSIONS);
+                assert!(pixel.in_bounds(DIMENSIONS));
+                assert_eq!(tile.world_pixel(pixel, DIMENSIONS), world_pixel);
+            }
+        }
+    }
+}
@@ -1,12 +1,51 @@
 pub use crate::CHUNK_SIZE;
 pub use crate::TILE_SIZE;
 
+pub use crate::dimensions::TilemapDimensions;
+
+pub use crate::events::{
+    ChunkCreatedEvent, ChunkLoadedEvent, ChunkUnloadedEvent, PixelChangedEvent, TileDeletedEvent,
+    TileDestroyedEvent, TileSetEvent,
+};
+
+pub use crate::access::{TilemapAccess, TilemapRead};
+
+pub use crate::tilemap::SetTileError;
 pub use crate::tilemap::Tilemap;
 pub use crate::tilemap::TilemapBundle;
 
+pub use crate::tile::ChunkTileLocation;
 pub use crate::tile::DeletingTile;
 pub use crate::tile::Tile;
 
-pub use crate::util::{world_unit_to_pixel, world_unit_to_tile};
+pub use crate::animation::{AnimatedTile, AnimationFrame, TileAnimations};
+pub use crate::autotile::{
+    AutoTile, AutoTileRule, AutoTileRules, AutoTileSet, AutoTileSetId, AutoTileSets, NeighborRule,
+    Neighbors,
+};
+pub use crate::image_tile::TileImageSource;
+pub use crate::tile_kind::{TileKind, TileRegistry};
+
+pub use crate::tileset::Tileset;
+
+pub use crate::overlap::{PixelMask, PixelOverlap, PixelShape, PixelSweepHit};
+pub use crate::raycast::{PixelRay, PixelRayHit};
+
+pub use crate::collider::{ChunkCollider, TilemapColliders};
+pub use crate::destruction::Destructible;
+pub use crate::material::{Material, MaterialFlags, MaterialId, MaterialRegistry};
+pub use crate::simulation::{FallingSand, FallingSandPlugin, PixelBehavior};
+
+pub use crate::position::{ChunkPos, LocalTilePos, PixelPos, TilePos, WorldPixelPos};
+
+pub use crate::util::{
+    tile_to_world_unit, world_pixel_to_world_unit, world_unit_to_pixel, world_unit_to_pixel_space,
+    world_unit_to_tile, world_unit_to_world_pixel,
+};
+
+pub use crate::generation::{ChunkGenerator, GeneratedChunk, TilemapGenerator};
+
+pub use crate::save::{SaveFormat, TilemapSave};
+pub use crate::streaming::{ChunkStreaming, StreamingAnchor};
 
 pub use crate::plugin::PixelPlugin;
@@ -0,0 +1,269 @@
+//! Pixel precise raycasts against a tilemap.
+//!
+//! Rays are cast in world pixel space, where world pixel `(x, y)` covers `x..x + 1` and `y..y + 1`.
+//! Distances are in pixels, and each ray has its own alpha threshold for pixels without a material.
+//! Rays stop once they leave the area covered by loaded chunks, so an infinite `max_distance` is fine.
+use bevy::prelude::*;
+
+use crate::{
+    chunk::Chunk,
+    material::MaterialRegistry,
+    position::{TilePos, WorldPixelPos},
+    tile::Tile,
+    tilemap::Tilemap,
+};
+
+/// A ray in world pixel space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelRay {
+    pub origin: Vec2,
+    /// Normalized when the ray is cast
+    pub direction: Vec2,
+    pub max_distance: f32,
+    /// Pixels without a material with an alpha above this stop the ray
+    pub alpha_threshold: f32,
+}
+
+impl PixelRay {
+    pub fn new(origin: Vec2, direction: Vec2, max_distance: f32) -> Self {
+        Self {
+            origin,
+            direction,
+            max_distance,
+            alpha_threshold: 0.0,
+        }
+    }
+
+    /// Only stops the ray on pixels without a material with an alpha above `alpha_threshold`
+    pub fn with_alpha_threshold(mut self, alpha_threshold: f32) -> Self {
+        self.alpha_threshold = alpha_threshold;
+        self
+    }
+}
+
+/// The first pixel a [`PixelRay`] hit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelRayHit {
+    pub world_pixel: WorldPixelPos,
+    pub tile: Entity,
+    /// The side of the pixel the ray entered through, zero if the ray started inside of the pixel
+    pub normal: IVec2,
+    /// The distance along the ray to the hit, in pixels
+    pub distance: f32,
+    /// The point the ray entered the pixel, in world pixel space
+    pub point: Vec2,
+}
+
+/// Walks the pixel grid along the ray, returning the first pixel that stops it
+pub fn raycast(
+    tilemap: &Tilemap,
+    ray: PixelRay,
+    materials: &MaterialRegistry,
+    chunks: &Query<&Chunk>,
+    tiles: &Query<&Tile>,
+) -> Option<PixelRayHit> {
+    let direction = ray.direction.normalize_or_zero();
+    if direction == Vec2::ZERO || !ray.origin.is_finite() || ray.max_distance.is_nan() {
+        return None;
+    }
+
+    let dimensions = tilemap.dimensions();
+    let max_distance = ray
+        .max_distance
+        .min(loaded_exit_distance(tilemap, ray.origin, direction)?);
+    let step = IVec2::new(direction.x.signum() as i32, direction.y.signum() as i32);
+    // The distance along the ray between two pixel borders on each axis
+    let delta = Vec2::new(
+        axis_delta(direction.x).unwrap_or(f32::INFINITY),
+        axis_delta(direction.y).unwrap_or(f32::INFINITY),
+    );
+
+    let mut pixel = ray.origin.floor().as_ivec2();
+    // The distance along the ray to the next pixel border on each axis
+    let mut next_border = Vec2::new(
+        first_border(ray.origin.x, pixel.x, direction.x),
+        first_border(ray.origin.y, pixel.y, direction.y),
+    );
+    let mut distance = 0.0;
+    let mut normal = IVec2::ZERO;
+
+    // Looking up the tile is the slow part, so it's only done when the ray enters a new tile
+    let mut current: Option<(TilePos, Option<(Entity, &Tile)>)> = None;
+
+    while distance <= max_distance {
+        let world_pixel = WorldPixelPos(pixel);
+        let (loc, tile_pixel) = world_pixel.split(dimensions);
+
+        if current.map(|(current, _)| current) != Some(loc) {
+            let tile = tilemap
+                .get_tile(loc, chunks)
+                .and_then(|entity| Some((entity, tiles.get(entity).ok()?)));
+            current = Some((loc, tile));
+        }
+
+        if let Some((_, Some((entity, tile)))) = current {
+            if tile.is_solid(tile_pixel, ray.alpha_threshold, materials) {
+                return Some(PixelRayHit {
+                    world_pixel,
+                    tile: entity,
+                    normal,
+                    distance,
+                    point: ray.origin + direction * distance,
+                });
+            }
+        }
+
+        if next_border.x < next_border.y {
+            pixel.x += step.x;
+            distance = next_border.x;
+            next_border.x += delta.x;
+            normal = IVec2::new(-step.x, 0);
+        } else {
+            pixel.y += step.y;
+            distance = next_border.y;
+            next_border.y += delta.y;
+            normal = IVec2::new(0, -step.y);
+        }
+    }
+
+    None
+}
+
+/// The distance along the ray at which it leaves the bounds of the loaded chunks,
+/// or `None` if it never passes through them
+fn loaded_exit_distance(tilemap: &Tilemap, origin: Vec2, direction: Vec2) -> Option<f32> {
+    let (min, max) = tilemap.chunks().map(|(loc, _)| loc.0).fold(
+        None,
+        |bounds: Option<(IVec2, IVec2)>, loc| {
+            Some(bounds.map_or((loc, loc), |(min, max)| (min.min(loc), max.max(loc))))
+        },
+    )?;
+    let chunk_pixels = tilemap.dimensions().chunk_pixels() as f32;
+    let min = min.as_vec2() * chunk_pixels;
+    let max = (max + IVec2::ONE).as_vec2() * chunk_pixels;
+
+    let mut exit = f32::INFINITY;
+    for axis in 0..2 {
+        if direction[axis] == 0.0 {
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let bound = if direction[axis] > 0.0 {
+            max[axis]
+        } else {
+            min[axis]
+        };
+        exit = exit.min((bound - origin[axis]) / direction[axis]);
+    }
+
+    (exit >= 0.0).then_some(exit)
+}
+
+fn axis_delta(direction: f32) -> Option<f32> {
+    (direction != 0.0).then(|| 1.0 / direction.abs())
+}
+
+fn first_border(origin: f32, pixel: i32, direction: f32) -> f32 {
+    if direction > 0.0 {
+        (pixel as f32 + 1.0 - origin) / direction
+    } else if direction < 0.0 {
+        (origin - pixel as f32) / -direction
+    } else {
+        f32::INFINITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::{
+        chunk::ChunkBundle,
+        dimensions::TilemapDimensions,
+        position::{ChunkPos, LocalTilePos},
+    };
+
+    /// A 4 by 4 tile chunk of 4 pixel tiles, with a single solid tile covering world pixels `(4, 0)` to `(7, 3)`
+    fn setup() -> (World, Tilemap) {
+        let dimensions = TilemapDimensions::new(4, 4);
+        let mut world = World::new();
+        let mut chunk = Chunk::with_dimensions(&mut Assets::default(), dimensions);
+
+        let tile = world
+            .spawn(Tile::from_color(Color::RED, dimensions.tile_size()))
+            .id();
+        chunk.insert_tile_entity(LocalTilePos::new(1, 0), tile);
+        let chunk = world.spawn(ChunkBundle::new(ChunkPos::ZERO, chunk)).id();
+
+        let mut tilemap = Tilemap::with_dimensions(dimensions);
+        tilemap.insert_chunk(ChunkPos::ZERO, chunk);
+
+        (world, tilemap)
+    }
+
+    fn cast(world: &mut World, tilemap: &Tilemap, ray: PixelRay) -> Option<PixelRayHit> {
+        let mut state = SystemState::<(Query<&Chunk>, Query<&Tile>)>::new(world);
+        let (chunks, tiles) = state.get(world);
+        raycast(tilemap, ray, &MaterialRegistry::default(), &chunks, &tiles)
+    }
+
+    #[test]
+    fn hits_first_pixel() {
+        let (mut world, tilemap) = setup();
+
+        let hit = cast(
+            &mut world,
+            &tilemap,
+            PixelRay::new(Vec2::new(0.5, 1.5), Vec2::X, 100.0),
+        )
+        .expect("The ray passes through the tile");
+        assert_eq!(hit.world_pixel, WorldPixelPos::new(4, 1));
+        assert_eq!(hit.normal, IVec2::NEG_X);
+        assert_eq!(hit.distance, 3.5);
+        assert_eq!(hit.point, Vec2::new(4.0, 1.5));
+
+        let hit = cast(
+            &mut world,
+            &tilemap,
+            PixelRay::new(Vec2::new(5.5, 10.0), Vec2::new(0.0, -1.0), 100.0),
+        )
+        .expect("The ray passes through the tile");
+        assert_eq!(hit.world_pixel, WorldPixelPos::new(5, 3));
+        assert_eq!(hit.normal, IVec2::Y);
+        assert_eq!(hit.distance, 6.0);
+    }
+
+    #[test]
+    fn starts_inside() {
+        let (mut world, tilemap) = setup();
+
+        let hit = cast(
+            &mut world,
+            &tilemap,
+            PixelRay::new(Vec2::new(6.5, 2.5), Vec2::Y, 100.0),
+        )
+        .expect("The ray starts in the tile");
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.normal, IVec2::ZERO);
+    }
+
+    #[test]
+    fn misses() {
+        let (mut world, tilemap) = setup();
+
+        for ray in [
+            PixelRay::new(Vec2::new(0.5, 1.5), Vec2::X, 3.0),
+            PixelRay::new(Vec2::new(0.5, 1.5), Vec2::NEG_X, 100.0),
+            PixelRay::new(Vec2::new(0.5, 1.5), Vec2::ZERO, 100.0),
+            PixelRay::new(Vec2::new(0.5, 8.5), Vec2::X, f32::INFINITY),
+            PixelRay::new(Vec2::new(0.5, 1.5), Vec2::X, f32::NAN),
+            PixelRay::new(Vec2::new(0.5, 1.5), Vec2::X, 100.0).with_alpha_threshold(1.0),
+        ] {
+            assert_eq!(cast(&mut world, &tilemap, ray), None, "{ray:?}");
+        }
+    }
+}
@@ -0,0 +1,928 @@
+//! Saving and loading whole tilemaps.
+//!
+//! A [`TilemapSave`] can be written as a compact binary file, or as RON for a human readable file.
+//!
+//! Components on tile entities are saved as well when their type is registered with
+//! `#[reflect(Component)]` in the app's [`AppTypeRegistry`]. [`AnimatedTile`](crate::animation::AnimatedTile)
+//! isn't, so animations are not saved.
+use std::{any::TypeId, collections::HashMap, fs, io, path::Path};
+
+use bevy::{
+    prelude::*,
+    reflect::{
+        serde::{ReflectSerializer, UntypedReflectDeserializer},
+        TypeRegistry,
+    },
+};
+use serde::{de::DeserializeSeed, Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    chunk::{Chunk, ChunkBundle},
+    dimensions::TilemapDimensions,
+    material::MaterialId,
+    multi_tile::{MultiTile, MultiTileMarker},
+    position::{ChunkPos, LocalTilePos, TilePos},
+    streaming::ChunkStreaming,
+    tile::{ChunkTileLocation, Tile, TileBundle},
+    tile_kind::{TileKind, TileRegistry},
+    tilemap::{Tilemap, TilemapBundle},
+};
+
+/// The current version of the save format.
+/// Older versions are converted to the current layout when they are loaded:
+/// - 1: tiles without components
+/// - 2: tiles without materials
+pub const SAVE_VERSION: u32 = 3;
+
+/// The first bytes of a binary save, followed by the version as a little endian `u32`
+const BINARY_MAGIC: &[u8; 4] = b"BPXM";
+
+#[derive(Error, Debug)]
+pub enum TilemapSaveError {
+    #[error("the data is not a binary tilemap save")]
+    InvalidHeader,
+    #[error("save version {0} is not supported, the newest supported version is {SAVE_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("invalid save data: {0}")]
+    InvalidData(String),
+    #[error("component {0} is not registered as a reflected component")]
+    UnregisteredComponent(String),
+    #[error("component {component} could not be serialized: {source}")]
+    SerializeComponent {
+        component: String,
+        source: ron::Error,
+    },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Binary(#[from] bincode::Error),
+    #[error(transparent)]
+    Ron(#[from] ron::Error),
+    #[error(transparent)]
+    RonParse(#[from] ron::error::SpannedError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveFormat {
+    /// A versioned header followed by bincode data
+    Binary,
+    /// Pretty printed RON
+    Ron,
+}
+
+/// A snapshot of a tilemap, its chunks, tiles and multi tiles
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TilemapSave {
+    pub version: u32,
+    pub chunk_size: usize,
+    pub tile_size: usize,
+    pub chunks: Vec<ChunkSave>,
+    pub multi_tiles: Vec<MultiTileSave>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChunkSave {
+    pub loc: ChunkPos,
+    pub tiles: Vec<TileSave>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TileSave {
+    pub loc: LocalTilePos,
+    /// RGBA8 pixels, row major
+    pub pixels: Vec<u8>,
+    /// The material of every pixel, in the same order as `pixels`.
+    /// Empty when none of the pixels have a material.
+    pub materials: Vec<MaterialId>,
+    /// Index into [`TilemapSave::multi_tiles`]
+    #[serde(default)]
+    pub multi_tile: Option<usize>,
+    /// Reflected components on the tile entity, each serialized as RON
+    pub components: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MultiTileSave {
+    pub pos: TilePos,
+    pub size: IVec2,
+    pub width: usize,
+    pub height: usize,
+    /// RGBA8 pixels, row major
+    pub pixels: Vec<u8>,
+}
+
+/// Only used to read the version of a RON save before parsing the rest of it
+#[derive(Deserialize)]
+struct SaveVersion {
+    version: u32,
+}
+
+impl TilemapSave {
+    /// Captures the current state of a tilemap.
+    /// Edits that are still queued on the tilemap are not included.
+    /// If the tilemap is [streamed](ChunkStreaming), the unloaded chunks in its store are included,
+    /// but chunks that [failed to load](ChunkStreaming::failed_chunks) are not.
+    pub fn capture(world: &World, tilemap_entity: Entity) -> Result<Self, TilemapSaveError> {
+        let Some(tilemap) = world.get::<Tilemap>(tilemap_entity) else {
+            return Err(TilemapSaveError::InvalidData(format!(
+                "{tilemap_entity:?} is not a tilemap"
+            )));
+        };
+        let registry = world.get_resource::<AppTypeRegistry>().cloned();
+        let registry = registry.as_ref().map(|registry| registry.read());
+        let dimensions = tilemap.dimensions();
+
+        let mut multi_tiles = MultiTileCapture::default();
+        let mut chunks = tilemap
+            .chunks()
+            .filter_map(|(loc, chunk)| {
+                ChunkSave::capture_with(
+                    world,
+                    chunk,
+                    loc,
+                    registry.as_deref(),
+                    Some(&mut multi_tiles),
+                )
+                .transpose()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let store = world
+            .get::<ChunkStreaming>(tilemap_entity)
+            .and_then(|streaming| streaming.store.as_deref());
+        if let Some(store) = store {
+            // A store can still have an older copy of a chunk that was loaded again
+            let unloaded = store
+                .chunks()?
+                .into_iter()
+                .filter(|chunk| tilemap.chunk_at(chunk.loc).is_none());
+            chunks.extend(unloaded);
+        }
+
+        // Chunks are stored in a hash map, sort them so saves are deterministic
+        chunks.sort_by_key(|chunk| (chunk.loc.0.x, chunk.loc.0.y));
+
+        Ok(Self {
+            version: SAVE_VERSION,
+            chunk_size: dimensions.chunk_size(),
+            tile_size: dimensions.tile_size(),
+            chunks,
+            multi_tiles: multi_tiles.saves,
+        })
+    }
+
+    /// Spawns a new tilemap with the saved chunks and tiles, returning the tilemap entity
+    pub fn spawn(&self, world: &mut World) -> Result<Entity, TilemapSaveError> {
+        check_version(self.version)?;
+        if self.chunk_size == 0 || self.tile_size == 0 {
+            return Err(TilemapSaveError::InvalidData(
+                "chunk and tile sizes must be greater than zero".to_string(),
+            ));
+        }
+
+        let dimensions = TilemapDimensions::new(self.chunk_size, self.tile_size);
+        for chunk in &self.chunks {
+            chunk.validate(dimensions, Some(self.multi_tiles.len()))?;
+        }
+        for multi_tile in &self.multi_tiles {
+            if multi_tile.pixels.len() != multi_tile.width * multi_tile.height * 4 {
+                return Err(TilemapSaveError::InvalidData(format!(
+                    "multi tile at {} has the wrong amount of pixels",
+                    multi_tile.pos
+                )));
+            }
+        }
+
+        // Deserialize every component first, so an unknown component doesn't leave a half spawned tilemap
+        let components = self
+            .chunks
+            .iter()
+            .map(|chunk| chunk.deserialize_components(world))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let tilemap_entity = world
+            .spawn(TilemapBundle::new(Tilemap::with_dimensions(dimensions)))
+            .id();
+
+        let multi_tile_entities = self
+            .multi_tiles
+            .iter()
+            .map(|_| world.spawn_empty().id())
+            .collect::<Vec<_>>();
+        let mut multi_tile_tiles = vec![vec![]; self.multi_tiles.len()];
+
+        for (chunk, components) in self.chunks.iter().zip(components) {
+            chunk.spawn_with(
+                world,
+                tilemap_entity,
+                dimensions,
+                components,
+                Some((&multi_tile_entities, &mut multi_tile_tiles)),
+            );
+        }
+
+        for ((multi_tile, entity), entities) in self
+            .multi_tiles
+            .iter()
+            .zip(multi_tile_entities)
+            .zip(multi_tile_tiles)
+        {
+            world.entity_mut(entity).insert(MultiTile {
+                pos: multi_tile.pos,
+                size: multi_tile.size,
+                pixels: decode_pixels(&multi_tile.pixels)
+                    .chunks(multi_tile.width.max(1))
+                    .map(|row| row.to_vec())
+                    .collect(),
+                entities,
+            });
+        }
+
+        Ok(tilemap_entity)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TilemapSaveError> {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bincode::serialize_into(&mut bytes, self)?;
+
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TilemapSaveError> {
+        if bytes.len() < 8 || &bytes[0..4] != BINARY_MAGIC {
+            return Err(TilemapSaveError::InvalidHeader);
+        }
+
+        let version = u32::from_le_bytes(bytes[4..8].try_into().expect("Slice is 4 bytes long"));
+        check_version(version)?;
+
+        let data = &bytes[8..];
+        Ok(match version {
+            1 => bincode::deserialize::<LegacySave<TileSaveV1>>(data)?.into(),
+            2 => bincode::deserialize::<LegacySave<TileSaveV2>>(data)?.into(),
+            _ => bincode::deserialize(data)?,
+        })
+    }
+
+    pub fn to_ron(&self) -> Result<String, TilemapSaveError> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, TilemapSaveError> {
+        let version = ron::from_str::<SaveVersion>(text)?.version;
+        check_version(version)?;
+
+        Ok(match version {
+            1 => ron::from_str::<LegacySave<TileSaveV1>>(text)?.into(),
+            2 => ron::from_str::<LegacySave<TileSaveV2>>(text)?.into(),
+            _ => ron::from_str(text)?,
+        })
+    }
+
+    pub fn save_to_file(
+        &self,
+        path: impl AsRef<Path>,
+        format: SaveFormat,
+    ) -> Result<(), TilemapSaveError> {
+        let bytes = match format {
+            SaveFormat::Binary => self.to_bytes()?,
+            SaveFormat::Ron => self.to_ron()?.into_bytes(),
+        };
+
+        Ok(fs::write(path, bytes)?)
+    }
+
+    /// Loads a save written in either format, the format is detected from the file's header
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self, TilemapSaveError> {
+        let bytes = fs::read(path)?;
+
+        if bytes.starts_with(BINARY_MAGIC) {
+            return Self::from_bytes(&bytes);
+        }
+
+        let text = String::from_utf8(bytes)
+            .map_err(|_| TilemapSaveError::InvalidData("the file is not valid RON".to_string()))?;
+        Self::from_ron(&text)
+    }
+}
+
+impl TileSave {
+    /// Whether the saved pixels and materials are the ones of the tile.
+    /// Colors are compared as they are saved, so a tile matches the save it was captured to.
+    fn matches(&self, tile: &Tile) -> bool {
+        let materials = if self.materials.is_empty() {
+            tile.materials().iter().all(|material| material.is_none())
+        } else {
+            self.materials == tile.materials()
+        };
+
+        materials && self.pixels == encode_pixels(tile.pixels())
+    }
+}
+
+impl ChunkSave {
+    /// Captures a single chunk of a tilemap.
+    /// Tiles that are part of a multi tile are saved as plain tiles.
+    pub fn capture(world: &World, chunk: Entity, loc: ChunkPos) -> Result<Self, TilemapSaveError> {
+        let registry = world.get_resource::<AppTypeRegistry>().cloned();
+        let registry = registry.as_ref().map(|registry| registry.read());
+
+        Self::capture_with(world, chunk, loc, registry.as_deref(), None)?
+            .ok_or_else(|| TilemapSaveError::InvalidData(format!("{chunk:?} is not a chunk")))
+    }
+
+    /// Spawns the chunk into an existing tilemap, returning the chunk entity.
+    /// Multi tile references are ignored.
+    pub fn spawn(&self, world: &mut World, tilemap: Entity) -> Result<Entity, TilemapSaveError> {
+        let Some(tilemap_component) = world.get::<Tilemap>(tilemap) else {
+            return Err(TilemapSaveError::InvalidData(format!(
+                "{tilemap:?} is not a tilemap"
+            )));
+        };
+        if tilemap_component.chunk_at(self.loc).is_some() {
+            return Err(TilemapSaveError::InvalidData(format!(
+                "the tilemap already has chunk {}",
+                self.loc
+            )));
+        }
+
+        let dimensions = tilemap_component.dimensions();
+        self.validate(dimensions, None)?;
+        let components = self.deserialize_components(world)?;
+
+        Ok(self.spawn_with(world, tilemap, dimensions, components, None))
+    }
+
+    fn capture_with(
+        world: &World,
+        chunk: Entity,
+        loc: ChunkPos,
+        registry: Option<&TypeRegistry>,
+        mut multi_tiles: Option<&mut MultiTileCapture>,
+    ) -> Result<Option<Self>, TilemapSaveError> {
+        let Some(chunk) = world.get::<Chunk>(chunk) else {
+            return Ok(None);
+        };
+
+        let mut tiles = vec![];
+        for (tile_loc, tile_entity) in chunk.tiles() {
+            let Some(tile) = world.get::<Tile>(tile_entity) else {
+                continue;
+            };
+
+            let multi_tile = multi_tiles.as_mut().and_then(|multi_tiles| {
+                let marker = world.get::<MultiTileMarker>(tile_entity)?;
+                multi_tiles.capture(world, marker.entity)
+            });
+
+            let components = match registry {
+                Some(registry) => capture_components(world, tile_entity, registry)?,
+                None => vec![],
+            };
+
+            tiles.push(TileSave {
+                loc: tile_loc,
+                pixels: encode_pixels(tile.pixels()),
+                materials: if tile.materials().iter().all(|material| material.is_none()) {
+                    vec![]
+                } else {
+                    tile.materials().to_vec()
+                },
+                multi_tile,
+                components,
+            });
+        }
+
+        Ok(Some(Self { loc, tiles }))
+    }
+
+    /// Spawns the chunk and its tiles, expects the save to be validated
+    fn spawn_with(
+        &self,
+        world: &mut World,
+        tilemap: Entity,
+        dimensions: TilemapDimensions,
+        components: Vec<Vec<LoadedComponent>>,
+        mut multi_tiles: Option<(&[Entity], &mut [Vec<Entity>])>,
+    ) -> Entity {
+        let chunk = Chunk::with_dimensions(&mut world.resource_mut::<Assets<Image>>(), dimensions);
+        let chunk_entity = world
+            .spawn(ChunkBundle::new(self.loc, chunk))
+            .set_parent(tilemap)
+            .id();
+        world
+            .get_mut::<Tilemap>(tilemap)
+            .expect("Tilemap should exist")
+            .insert_chunk(self.loc, chunk_entity);
+
+        let mut placed = vec![];
+        for (tile_save, components) in self.tiles.iter().zip(components) {
+            let loc = self.loc.tile(tile_save.loc, dimensions);
+            let pixels = decode_pixels(&tile_save.pixels);
+            let tile = if tile_save.materials.is_empty() {
+                Tile::from_pixel_vec(dimensions.tile_size(), pixels)
+            } else {
+                Tile::from_pixel_vec_with_materials(
+                    dimensions.tile_size(),
+                    pixels,
+                    tile_save.materials.clone(),
+                )
+            };
+
+            let mut tile_entity =
+                world.spawn((TileBundle::new(tile, loc), ChunkTileLocation(tile_save.loc)));
+            tile_entity.set_parent(chunk_entity);
+
+            if let (Some(index), Some((entities, tiles))) =
+                (tile_save.multi_tile, multi_tiles.as_mut())
+            {
+                tile_entity.insert(MultiTileMarker {
+                    entity: entities[index],
+                });
+                tiles[index].push(tile_entity.id());
+            }
+
+            for (component, reflect_component) in components {
+                reflect_component.insert(&mut tile_entity, &*component);
+            }
+
+            placed.push((tile_save.loc, tile_entity.id()));
+        }
+
+        share_kind_pixels(
+            world,
+            placed
+                .iter()
+                .zip(&self.tiles)
+                .map(|((_, entity), tile_save)| (*entity, tile_save)),
+        );
+
+        let mut chunk = world
+            .get_mut::<Chunk>(chunk_entity)
+            .expect("Chunk was just spawned");
+        for (loc, entity) in placed {
+            chunk.insert_tile_entity(loc, entity);
+        }
+
+        chunk_entity
+    }
+
+    /// Deserializes the components of every tile, in the same order as the tiles
+    fn deserialize_components(
+        &self,
+        world: &World,
+    ) -> Result<Vec<Vec<LoadedComponent>>, TilemapSaveError> {
+        let registry = world.get_resource::<AppTypeRegistry>().cloned();
+        let registry = registry.as_ref().map(|registry| registry.read());
+
+        self.tiles
+            .iter()
+            .map(|tile| deserialize_components(&tile.components, registry.as_deref()))
+            .collect()
+    }
+
+    /// Checks the tiles fit the dimensions.
+    /// Multi tile references are only checked when the amount of multi tiles is given.
+    fn validate(
+        &self,
+        dimensions: TilemapDimensions,
+        multi_tile_count: Option<usize>,
+    ) -> Result<(), TilemapSaveError> {
+        let tile_bytes = dimensions.tile_size() * dimensions.tile_size() * 4;
+
+        for tile in &self.tiles {
+            if !tile.loc.in_bounds(dimensions) {
+                return Err(TilemapSaveError::InvalidData(format!(
+                    "tile {} is outside of chunk {}",
+                    tile.loc, self.loc
+                )));
+            }
+
+            if tile.pixels.len() != tile_bytes {
+                return Err(TilemapSaveError::InvalidData(format!(
+                    "tile {} in chunk {} has {} bytes of pixels, expected {}",
+                    tile.loc,
+                    self.loc,
+                    tile.pixels.len(),
+                    tile_bytes
+                )));
+            }
+
+            if !tile.materials.is_empty() && tile.materials.len() * 4 != tile_bytes {
+                return Err(TilemapSaveError::InvalidData(format!(
+                    "tile {} in chunk {} has {} materials, expected {}",
+                    tile.loc,
+                    self.loc,
+                    tile.materials.len(),
+                    tile_bytes / 4
+                )));
+            }
+
+            if let (Some(index), Some(count)) = (tile.multi_tile, multi_tile_count) {
+                if index >= count {
+                    return Err(TilemapSaveError::InvalidData(format!(
+                        "tile {} in chunk {} references a missing multi tile",
+                        tile.loc, self.loc
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn check_version(version: u32) -> Result<(), TilemapSaveError> {
+    if version == 0 || version > SAVE_VERSION {
+        return Err(TilemapSaveError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+/// The multi tiles referenced by the tiles captured so far
+#[derive(Default)]
+struct MultiTileCapture {
+    saves: Vec<MultiTileSave>,
+    indices: HashMap<Entity, usize>,
+}
+
+impl MultiTileCapture {
+    /// Adds the multi tile to the save if it isn't already in it, returning its index
+    fn capture(&mut self, world: &World, entity: Entity) -> Option<usize> {
+        if let Some(index) = self.indices.get(&entity) {
+            return Some(*index);
+        }
+
+        let multi_tile = world.get::<MultiTile>(entity)?;
+        let pixels = multi_tile
+            .pixels
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        self.saves.push(MultiTileSave {
+            pos: multi_tile.pos,
+            size: multi_tile.size,
+            width: multi_tile.pixels.first().map(|row| row.len()).unwrap_or(0),
+            height: multi_tile.pixels.len(),
+            pixels: encode_pixels(&pixels),
+        });
+        self.indices.insert(entity, self.saves.len() - 1);
+
+        Some(self.saves.len() - 1)
+    }
+}
+
+/// Gives loaded tiles of a registered kind that still have the registered pixels the registered tile,
+/// so they share its pixels again instead of each keeping a copy
+fn share_kind_pixels<'a>(world: &mut World, tiles: impl Iterator<Item = (Entity, &'a TileSave)>) {
+    let Some(registry) = world.get_resource::<TileRegistry>() else {
+        return;
+    };
+
+    let shared = tiles
+        .filter_map(|(entity, tile_save)| {
+            let registered = registry.tile(*world.get::<TileKind>(entity)?)?;
+            tile_save
+                .matches(&registered)
+                .then_some((entity, registered))
+        })
+        .collect::<Vec<_>>();
+
+    for (entity, tile) in shared {
+        world.entity_mut(entity).insert(tile);
+    }
+}
+
+/// Serializes every reflected component on the tile entity.
+/// The transform and hierarchy are rebuilt when loading, so they are skipped.
+fn capture_components(
+    world: &World,
+    entity: Entity,
+    registry: &TypeRegistry,
+) -> Result<Vec<String>, TilemapSaveError> {
+    let skipped = [
+        TypeId::of::<Transform>(),
+        TypeId::of::<GlobalTransform>(),
+        TypeId::of::<Parent>(),
+        TypeId::of::<Children>(),
+    ];
+
+    let entity = world.entity(entity);
+    entity
+        .archetype()
+        .components()
+        .filter_map(|component_id| world.components().get_info(component_id)?.type_id())
+        .filter(|type_id| !skipped.contains(type_id))
+        .filter_map(|type_id| {
+            let reflect_component = registry.get_type_data::<ReflectComponent>(type_id)?;
+            let component = reflect_component.reflect(entity)?;
+
+            Some(
+                ron::to_string(&ReflectSerializer::new(component, registry)).map_err(|source| {
+                    TilemapSaveError::SerializeComponent {
+                        component: component.reflect_type_path().to_string(),
+                        source,
+                    }
+                }),
+            )
+        })
+        .collect()
+}
+
+/// A deserialized component and the type data used to insert it
+type LoadedComponent = (Box<dyn Reflect>, ReflectComponent);
+
+fn deserialize_components(
+    components: &[String],
+    registry: Option<&TypeRegistry>,
+) -> Result<Vec<LoadedComponent>, TilemapSaveError> {
+    let Some(registry) = registry else {
+        return match components.first() {
+            Some(component) => Err(TilemapSaveError::UnregisteredComponent(component.clone())),
+            None => Ok(vec![]),
+        };
+    };
+
+    components
+        .iter()
+        .map(|component| {
+            let mut deserializer = ron::Deserializer::from_str(component)?;
+            let value = UntypedReflectDeserializer::new(registry).deserialize(&mut deserializer)?;
+
+            let type_path = value
+                .get_represented_type_info()
+                .map(|info| info.type_path())
+                .unwrap_or_else(|| value.reflect_type_path());
+            let reflect_component = registry
+                .get_with_type_path(type_path)
+                .and_then(|registration| registration.data::<ReflectComponent>())
+                .ok_or_else(|| TilemapSaveError::UnregisteredComponent(type_path.to_string()))?;
+
+            Ok((value, reflect_component.clone()))
+        })
+        .collect()
+}
+
+/// A save in the layout of an older version, with the tiles in the layout of that version
+#[derive(Deserialize)]
+struct LegacySave<T> {
+    /// Checked before the rest of the save is decoded
+    #[serde(rename = "version")]
+    _version: u32,
+    chunk_size: usize,
+    tile_size: usize,
+    chunks: Vec<LegacyChunkSave<T>>,
+    multi_tiles: Vec<MultiTileSave>,
+}
+
+#[derive(Deserialize)]
+struct LegacyChunkSave<T> {
+    loc: ChunkPos,
+    tiles: Vec<T>,
+}
+
+impl<T: Into<TileSave>> From<LegacySave<T>> for TilemapSave {
+    fn from(save: LegacySave<T>) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            chunk_size: save.chunk_size,
+            tile_size: save.tile_size,
+            chunks: save
+                .chunks
+                .into_iter()
+                .map(|chunk| ChunkSave {
+                    loc: chunk.loc,
+                    tiles: chunk.tiles.into_iter().map(Into::into).collect(),
+                })
+                .collect(),
+            multi_tiles: save.multi_tiles,
+        }
+    }
+}
+
+/// A tile in version 1, before components were saved
+#[derive(Deserialize)]
+struct TileSaveV1 {
+    loc: LocalTilePos,
+    pixels: Vec<u8>,
+    #[serde(default)]
+    multi_tile: Option<usize>,
+}
+
+impl From<TileSaveV1> for TileSave {
+    fn from(tile: TileSaveV1) -> Self {
+        Self {
+            loc: tile.loc,
+            pixels: tile.pixels,
+            materials: vec![],
+            multi_tile: tile.multi_tile,
+            components: vec![],
+        }
+    }
+}
+
+/// A tile in version 2, before materials were saved
+#[derive(Deserialize)]
+struct TileSaveV2 {
+    loc: LocalTilePos,
+    pixels: Vec<u8>,
+    #[serde(default)]
+    multi_tile: Option<usize>,
+    components: Vec<String>,
+}
+
+impl From<TileSaveV2> for TileSave {
+    fn from(tile: TileSaveV2) -> Self {
+        Self {
+            loc: tile.loc,
+            pixels: tile.pixels,
+            materials: vec![],
+            multi_tile: tile.multi_tile,
+            components: tile.components,
+        }
+    }
+}
+
+fn encode_pixels(pixels: &[Color]) -> Vec<u8> {
+    pixels.iter().flat_map(|pixel| pixel.as_rgba_u8()).collect()
+}
+
+fn decode_pixels(bytes: &[u8]) -> Vec<Color> {
+    bytes
+        .chunks_exact(4)
+        .map(|pixel| Color::rgba_u8(pixel[0], pixel[1], pixel[2], pixel[3]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile_save(loc: LocalTilePos, color: Color, materials: Vec<MaterialId>) -> TileSave {
+        TileSave {
+            loc,
+            pixels: encode_pixels(&[color; 4]),
+            materials,
+            multi_tile: None,
+            components: vec![],
+        }
+    }
+
+    fn save() -> TilemapSave {
+        TilemapSave {
+            version: SAVE_VERSION,
+            chunk_size: 2,
+            tile_size: 2,
+            chunks: vec![
+                ChunkSave {
+                    loc: ChunkPos::new(-1, 0),
+                    tiles: vec![tile_save(LocalTilePos::new(1, 0), Color::RED, vec![])],
+                },
+                ChunkSave {
+                    loc: ChunkPos::new(0, 0),
+                    tiles: vec![tile_save(
+                        LocalTilePos::new(0, 1),
+                        Color::BLUE,
+                        vec![
+                            MaterialId(1),
+                            MaterialId::NONE,
+                            MaterialId(2),
+                            MaterialId(1),
+                        ],
+                    )],
+                },
+            ],
+            multi_tiles: vec![],
+        }
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let save = save();
+        let bytes = save.to_bytes().unwrap();
+        assert_eq!(&bytes[0..4], BINARY_MAGIC);
+        assert_eq!(TilemapSave::from_bytes(&bytes).unwrap(), save);
+
+        assert!(matches!(
+            TilemapSave::from_bytes(b"nope"),
+            Err(TilemapSaveError::InvalidHeader)
+        ));
+        let mut future = bytes.clone();
+        future[4..8].copy_from_slice(&(SAVE_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            TilemapSave::from_bytes(&future),
+            Err(TilemapSaveError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let save = save();
+        assert_eq!(
+            TilemapSave::from_ron(&save.to_ron().unwrap()).unwrap(),
+            save
+        );
+    }
+
+    #[test]
+    fn spawn_and_capture() {
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+
+        let save = save();
+        let tilemap = save.spawn(&mut world).unwrap();
+        assert_eq!(TilemapSave::capture(&world, tilemap).unwrap(), save);
+    }
+
+    /// The layout of older saves, for writing them the way older versions did
+    #[derive(Serialize)]
+    struct OldSave<T> {
+        version: u32,
+        chunk_size: usize,
+        tile_size: usize,
+        chunks: Vec<(ChunkPos, Vec<T>)>,
+        multi_tiles: Vec<MultiTileSave>,
+    }
+
+    fn old_bytes<T: Serialize>(version: u32, tiles: Vec<T>) -> Vec<u8> {
+        let save = OldSave {
+            version,
+            chunk_size: 2,
+            tile_size: 2,
+            chunks: vec![(ChunkPos::new(3, -2), tiles)],
+            multi_tiles: vec![],
+        };
+
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bincode::serialize_into(&mut bytes, &save).unwrap();
+        bytes
+    }
+
+    fn legacy_save(components: Vec<String>) -> TilemapSave {
+        TilemapSave {
+            version: SAVE_VERSION,
+            chunk_size: 2,
+            tile_size: 2,
+            chunks: vec![ChunkSave {
+                loc: ChunkPos::new(3, -2),
+                tiles: vec![TileSave {
+                    loc: LocalTilePos::new(1, 1),
+                    pixels: encode_pixels(&[Color::GREEN; 4]),
+                    materials: vec![],
+                    multi_tile: None,
+                    components,
+                }],
+            }],
+            multi_tiles: vec![],
+        }
+    }
+
+    #[test]
+    fn version_1_without_components() {
+        let pixels = encode_pixels(&[Color::GREEN; 4]);
+        let bytes = old_bytes(1, vec![(LocalTilePos::new(1, 1), pixels, None::<usize>)]);
+        assert_eq!(
+            TilemapSave::from_bytes(&bytes).unwrap(),
+            legacy_save(vec![])
+        );
+
+        let text = "(version: 1, chunk_size: 2, tile_size: 2, chunks: [(loc: (0, 0), tiles: [(loc: (1, 0), pixels: [0, 0, 0, 255])])], multi_tiles: [])";
+        let save = TilemapSave::from_ron(text).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.chunks[0].tiles[0].loc, LocalTilePos::new(1, 0));
+        assert!(save.chunks[0].tiles[0].components.is_empty());
+    }
+
+    #[test]
+    fn version_2_without_materials() {
+        let components = vec!["()".to_string()];
+        let pixels = encode_pixels(&[Color::GREEN; 4]);
+        let bytes = old_bytes(
+            2,
+            vec![(
+                LocalTilePos::new(1, 1),
+                pixels,
+                None::<usize>,
+                components.clone(),
+            )],
+        );
+        assert_eq!(
+            TilemapSave::from_bytes(&bytes).unwrap(),
+            legacy_save(components)
+        );
+
+        let text = "(version: 2, chunk_size: 2, tile_size: 2, chunks: [(loc: (0, 0), tiles: [(loc: (1, 0), pixels: [], multi_tile: Some(0), components: [\"()\"])])], multi_tiles: [])";
+        let save = TilemapSave::from_ron(text).unwrap();
+        assert_eq!(save.chunks[0].tiles[0].multi_tile, Some(0));
+        assert!(save.chunks[0].tiles[0].materials.is_empty());
+        assert_eq!(save.chunks[0].tiles[0].components, vec!["()".to_string()]);
+    }
+}
@@ -0,0 +1,522 @@
+//! Falling sand pixel physics, where powders fall, liquids flow and gases rise.
+//!
+//! Add [`FallingSandPlugin`] to the app and [`FallingSand`] to a tilemap entity.
+//! Pixels are stepped on the [`FixedUpdate`] schedule, so the rate is set with `Time<Fixed>`.
+//! Chunks without any movement go to sleep until a tile or pixel in or next to them is changed
+//! through the tilemap, call [`FallingSand::wake`] after changing a [`Tile`] directly.
+//! Pixels don't move into chunks that don't exist.
+//!
+//! Pixels made of a material move by the material's flags, denser materials sinking through lighter
+//! moving ones. Pixels without a material move by their color, see [`FallingSand::behaviors`].
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::{
+    chunk::Chunk,
+    events::{
+        ChunkCreatedEvent, ChunkLoadedEvent, PixelChangedEvent, TileDeletedEvent, TileSetEvent,
+    },
+    material::{MaterialFlags, MaterialId, MaterialRegistry},
+    position::{ChunkPos, PixelPos, TilePos, WorldPixelPos},
+    tile::Tile,
+    tilemap::{tilemap_event_system, Tilemap},
+};
+
+pub struct FallingSandPlugin;
+
+impl Plugin for FallingSandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, falling_sand_step)
+            .add_systems(PostUpdate, falling_sand_wake.after(tilemap_event_system));
+    }
+}
+
+/// How a pixel moves
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PixelBehavior {
+    /// Never moves
+    #[default]
+    Static,
+    /// Falls straight down or diagonally, sinking through liquids and gases
+    Powder,
+    /// Falls, then flows sideways, sinking through gases
+    Liquid,
+    /// Rises, then spreads sideways
+    Gas,
+}
+
+impl PixelBehavior {
+    /// How pixels of a material with these flags move
+    pub fn from_flags(flags: MaterialFlags) -> Self {
+        if flags.contains(MaterialFlags::POWDER) {
+            PixelBehavior::Powder
+        } else if flags.contains(MaterialFlags::LIQUID) {
+            PixelBehavior::Liquid
+        } else if flags.contains(MaterialFlags::GAS) {
+            PixelBehavior::Gas
+        } else {
+            PixelBehavior::Static
+        }
+    }
+
+    /// The directions the pixel tries to move in, in order
+    fn moves(self, flip: bool) -> &'static [IVec2] {
+        const POWDER: [IVec2; 3] = [IVec2::NEG_Y, IVec2::new(-1, -1), IVec2::new(1, -1)];
+        const POWDER_FLIPPED: [IVec2; 3] = [IVec2::NEG_Y, IVec2::new(1, -1), IVec2::new(-1, -1)];
+        const LIQUID: [IVec2; 5] = [
+            IVec2::NEG_Y,
+            IVec2::new(-1, -1),
+            IVec2::new(1, -1),
+            IVec2::NEG_X,
+            IVec2::X,
+        ];
+        const LIQUID_FLIPPED: [IVec2; 5] = [
+            IVec2::NEG_Y,
+            IVec2::new(1, -1),
+            IVec2::new(-1, -1),
+            IVec2::X,
+            IVec2::NEG_X,
+        ];
+        const GAS: [IVec2; 5] = [
+            IVec2::Y,
+            IVec2::new(-1, 1),
+            IVec2::new(1, 1),
+            IVec2::NEG_X,
+            IVec2::X,
+        ];
+        const GAS_FLIPPED: [IVec2; 5] = [
+            IVec2::Y,
+            IVec2::new(1, 1),
+            IVec2::new(-1, 1),
+            IVec2::X,
+            IVec2::NEG_X,
+        ];
+
+        match (self, flip) {
+            (PixelBehavior::Static, _) => &[],
+            (PixelBehavior::Powder, false) => &POWDER,
+            (PixelBehavior::Powder, true) => &POWDER_FLIPPED,
+            (PixelBehavior::Liquid, false) => &LIQUID,
+            (PixelBehavior::Liquid, true) => &LIQUID_FLIPPED,
+            (PixelBehavior::Gas, false) => &GAS,
+            (PixelBehavior::Gas, true) => &GAS_FLIPPED,
+        }
+    }
+
+    /// Whether a pixel with this behavior can swap places with `other`
+    fn displaces(self, other: PixelBehavior) -> bool {
+        matches!(
+            (self, other),
+            (
+                PixelBehavior::Powder,
+                PixelBehavior::Liquid | PixelBehavior::Gas
+            ) | (PixelBehavior::Liquid, PixelBehavior::Gas)
+        )
+    }
+}
+
+/// Simulates the pixels of a tilemap
+#[derive(Component, Clone, Debug)]
+pub struct FallingSand {
+    /// How pixels without a material behave by their RGBA8 color, colors that aren't listed are static
+    pub behaviors: HashMap<[u8; 4], PixelBehavior>,
+    /// How many steps a chunk stays awake without any movement
+    pub sleep_delay: u32,
+    /// Awake chunks, and the steps they have left before sleeping
+    awake: HashMap<ChunkPos, u32>,
+    /// Empty tiles that pixels moved into, which are created by the tilemap's event queue
+    in_flight: HashSet<TilePos>,
+    step: u64,
+}
+
+impl Default for FallingSand {
+    fn default() -> Self {
+        Self {
+            behaviors: HashMap::new(),
+            sleep_delay: 8,
+            awake: HashMap::new(),
+            in_flight: HashSet::new(),
+            step: 0,
+        }
+    }
+}
+
+impl FallingSand {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes pixels of `color` move with `behavior`
+    pub fn with_behavior(mut self, color: Color, behavior: PixelBehavior) -> Self {
+        self.behaviors.insert(color.as_rgba_u8(), behavior);
+        self
+    }
+
+    /// The behavior of a pixel without a material, `None` for transparent pixels
+    pub fn behavior(&self, color: Color) -> Option<PixelBehavior> {
+        if color.a() <= 0.0 {
+            return None;
+        }
+
+        Some(
+            self.behaviors
+                .get(&color.as_rgba_u8())
+                .copied()
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Wakes the chunk and the chunks around it
+    pub fn wake(&mut self, chunk: ChunkPos) {
+        for y in -1..=1 {
+            for x in -1..=1 {
+                self.awake
+                    .insert(chunk + IVec2::new(x, y), self.sleep_delay);
+            }
+        }
+    }
+
+    pub fn is_awake(&self, chunk: ChunkPos) -> bool {
+        self.awake.contains_key(&chunk)
+    }
+}
+
+/// A visible pixel
+#[derive(Clone, Copy)]
+struct Particle {
+    color: Color,
+    material: MaterialId,
+    behavior: PixelBehavior,
+    /// Only pixels made of a registered material have a density
+    density: Option<f32>,
+}
+
+impl Particle {
+    /// Whether this particle can swap places with `other`
+    fn displaces(&self, other: &Particle) -> bool {
+        match (self.density, other.density) {
+            (Some(density), Some(other_density)) => {
+                other.behavior != PixelBehavior::Static
+                    && self.behavior != PixelBehavior::Gas
+                    && density > other_density
+            }
+            _ => self.behavior.displaces(other.behavior),
+        }
+    }
+}
+
+/// What is at a world pixel, as far as moving pixels are concerned
+enum Cell {
+    /// Nothing can move here
+    Blocked,
+    /// There's no tile, a tile is created if a pixel moves here
+    Missing,
+    Pixel {
+        tile: Entity,
+        /// `None` for transparent pixels
+        particle: Option<Particle>,
+    },
+}
+
+/// The state of one step of a tilemap
+struct Step<'a> {
+    tilemap: &'a mut Tilemap,
+    sand: &'a mut FallingSand,
+    materials: &'a MaterialRegistry,
+    /// Pixels that were moved into this step, so they don't move twice
+    moved: HashSet<WorldPixelPos>,
+    /// Chunks that had movement this step
+    active: HashSet<ChunkPos>,
+}
+
+impl<'a> Step<'a> {
+    fn particle(&self, tile: &Tile, pixel: PixelPos) -> Option<Particle> {
+        let color = tile.get_pixel(pixel)?;
+        if color.a() <= 0.0 {
+            return None;
+        }
+
+        let material = tile.get_material(pixel).unwrap_or_default();
+        let particle = match self.materials.get(material) {
+            Some(registered) => Particle {
+                color,
+                material,
+                behavior: PixelBehavior::from_flags(registered.flags),
+                density: Some(registered.density),
+            },
+            None => Particle {
+                color,
+                material,
+                behavior: self.sand.behavior(color).unwrap_or_default(),
+                density: None,
+            },
+        };
+
+        Some(particle)
+    }
+
+    fn cell(
+        &self,
+        world_pixel: WorldPixelPos,
+        chunks: &Query<&Chunk>,
+        tiles: &Query<&mut Tile>,
+    ) -> Cell {
+        let dimensions = self.tilemap.dimensions();
+        let (loc, pixel) = world_pixel.split(dimensions);
+        if self.sand.in_flight.contains(&loc) || !self.tilemap.has_chunk(loc) {
+            return Cell::Blocked;
+        }
+
+        let Some(tile) = self.tilemap.get_tile(loc, chunks) else {
+            return Cell::Missing;
+        };
+        let Ok(tile_data) = tiles.get(tile) else {
+            return Cell::Blocked;
+        };
+
+        Cell::Pixel {
+            tile,
+            particle: self.particle(tile_data, pixel),
+        }
+    }
+
+    fn set_pixel(
+        &self,
+        tile: Entity,
+        world_pixel: WorldPixelPos,
+        particle: Option<Particle>,
+        tiles: &mut Query<&mut Tile>,
+    ) {
+        let pixel = world_pixel.pixel(self.tilemap.dimensions());
+        if let Ok(mut tile) = tiles.get_mut(tile) {
+            match particle {
+                Some(particle) => tile.set_material(pixel, particle.material, particle.color),
+                None => tile.set_material(pixel, MaterialId::NONE, Color::NONE),
+            }
+        }
+    }
+
+    /// Tries to move the pixel, returns whether it moved
+    fn move_pixel(
+        &mut self,
+        tile: Entity,
+        world_pixel: WorldPixelPos,
+        particle: Particle,
+        chunks: &Query<&Chunk>,
+        tiles: &mut Query<&mut Tile>,
+    ) -> bool {
+        // Alternate the preferred direction per step and pixel, so piles stay symmetric
+        let flip = (self.sand.step as i32 ^ world_pixel.0.x ^ world_pixel.0.y) & 1 == 1;
+        for offset in particle.behavior.moves(flip) {
+            let target = world_pixel + *offset;
+            if self.moved.contains(&target) {
+                continue;
+            }
+
+            match self.cell(target, chunks, tiles) {
+                Cell::Blocked => continue,
+                Cell::Missing => {
+                    self.set_pixel(tile, world_pixel, None, tiles);
+                    self.tilemap.set_world_pixels(
+                        [(target, particle.color, particle.material)],
+                        true,
+                        false,
+                    );
+                    self.sand
+                        .in_flight
+                        .insert(target.tile(self.tilemap.dimensions()));
+                }
+                Cell::Pixel {
+                    tile: target_tile,
+                    particle: target_particle,
+                } => {
+                    let free = match &target_particle {
+                        None => true,
+                        Some(target_particle) => particle.displaces(target_particle),
+                    };
+                    if !free {
+                        continue;
+                    }
+
+                    self.set_pixel(tile, world_pixel, target_particle, tiles);
+                    self.set_pixel(target_tile, target, Some(particle), tiles);
+                }
+            }
+
+            self.moved.insert(target);
+            self.wake_around(world_pixel);
+            self.wake_around(target);
+            return true;
+        }
+
+        false
+    }
+
+    fn wake_around(&mut self, world_pixel: WorldPixelPos) {
+        let dimensions = self.tilemap.dimensions();
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let chunk = (world_pixel + IVec2::new(x, y))
+                    .tile(dimensions)
+                    .chunk(dimensions);
+                self.active.insert(chunk);
+            }
+        }
+    }
+
+    fn step_chunk(
+        &mut self,
+        chunk_loc: ChunkPos,
+        chunk: Entity,
+        chunks: &Query<&Chunk>,
+        tiles: &mut Query<&mut Tile>,
+    ) {
+        let Ok(chunk) = chunks.get(chunk) else {
+            return;
+        };
+
+        let dimensions = self.tilemap.dimensions();
+        let tile_size = dimensions.tile_size() as i32;
+        let flip = self.sand.step % 2 == 1;
+
+        let mut chunk_tiles = chunk.tiles().collect::<Vec<_>>();
+        // Bottom to top, so falling pixels make room for the ones above them
+        chunk_tiles.sort_by_key(|(loc, _)| (loc.0.y, if flip { -loc.0.x } else { loc.0.x }));
+
+        for (local, tile) in chunk_tiles {
+            let loc = chunk_loc.tile(local, dimensions);
+
+            for pixel_y in (0..tile_size).rev() {
+                for pixel_x in 0..tile_size {
+                    let pixel_x = if flip {
+                        tile_size - 1 - pixel_x
+                    } else {
+                        pixel_x
+                    };
+                    let world_pixel = loc.world_pixel(PixelPos::new(pixel_x, pixel_y), dimensions);
+                    if self.moved.contains(&world_pixel) {
+                        continue;
+                    }
+
+                    let Some(particle) = tiles
+                        .get(tile)
+                        .ok()
+                        .and_then(|tile| self.particle(tile, world_pixel.pixel(dimensions)))
+                    else {
+                        continue;
+                    };
+                    if particle.behavior == PixelBehavior::Static {
+                        continue;
+                    }
+
+                    self.move_pixel(tile, world_pixel, particle, chunks, tiles);
+                }
+            }
+        }
+    }
+}
+
+/// Steps every awake chunk of every simulated tilemap once
+pub fn falling_sand_step(
+    mut tilemaps: Query<(&mut Tilemap, &mut FallingSand)>,
+    chunks: Query<&Chunk>,
+    mut tiles: Query<&mut Tile>,
+    materials: Res<MaterialRegistry>,
+) {
+    for (mut tilemap, mut sand) in &mut tilemaps {
+        let sand = sand.as_mut();
+        sand.step = sand.step.wrapping_add(1);
+        sand.in_flight
+            .retain(|loc| tilemap.get_tile(*loc, &chunks).is_none() && tilemap.has_chunk(*loc));
+
+        let mut awake = sand.awake.keys().copied().collect::<Vec<_>>();
+        awake.sort_by_key(|chunk| (chunk.0.y, chunk.0.x));
+
+        let mut step = Step {
+            tilemap: &mut tilemap,
+            sand: &mut *sand,
+            materials: &materials,
+            moved: HashSet::new(),
+            active: HashSet::new(),
+        };
+
+        for chunk_loc in awake {
+            match step.tilemap.chunk_at(chunk_loc) {
+                Some(chunk) => step.step_chunk(chunk_loc, chunk, &chunks, &mut tiles),
+                None => {
+                    step.sand.awake.remove(&chunk_loc);
+                }
+            }
+        }
+
+        let active = step.active;
+        // Chunks with pixels in flight stay awake until the pixels land
+        let dimensions = tilemap.dimensions();
+        let active = active
+            .into_iter()
+            .chain(sand.in_flight.iter().map(|loc| loc.chunk(dimensions)))
+            .collect::<HashSet<_>>();
+
+        sand.awake.retain(|chunk, steps_left| {
+            if active.contains(chunk) {
+                return true;
+            }
+            *steps_left = steps_left.saturating_sub(1);
+            *steps_left > 0
+        });
+        for chunk in active {
+            if tilemap.chunk_at(chunk).is_some() {
+                sand.awake.insert(chunk, sand.sleep_delay);
+            }
+        }
+    }
+}
+
+/// Wakes chunks around tiles and pixels changed through the tilemap
+pub fn falling_sand_wake(
+    mut tilemaps: Query<(&Tilemap, &mut FallingSand)>,
+    mut chunk_created: EventReader<ChunkCreatedEvent>,
+    mut chunk_loaded: EventReader<ChunkLoadedEvent>,
+    mut tile_set: EventReader<TileSetEvent>,
+    mut tile_deleted: EventReader<TileDeletedEvent>,
+    mut pixel_changed: EventReader<PixelChangedEvent>,
+) {
+    for (tilemap, mut sand) in &mut tilemaps {
+        if sand.is_added() {
+            for (chunk, _) in tilemap.chunks() {
+                sand.wake(chunk);
+            }
+        }
+    }
+
+    let mut wake = |tilemap: Entity, chunk: ChunkPos| {
+        if let Ok((_, mut sand)) = tilemaps.get_mut(tilemap) {
+            sand.wake(chunk);
+        }
+    };
+
+    for event in chunk_created.read() {
+        wake(event.tilemap, event.loc);
+    }
+    for event in chunk_loaded.read() {
+        wake(event.tilemap, event.loc);
+    }
+
+    let mut tile_chunks = tile_set
+        .read()
+        .map(|event| (event.tilemap, event.loc))
+        .chain(tile_deleted.read().map(|event| (event.tilemap, event.loc)))
+        .chain(pixel_changed.read().map(|event| (event.tilemap, event.loc)))
+        .collect::<Vec<_>>();
+    // Pixel edits come in bulk, only wake each tile once
+    tile_chunks.sort_by_key(|(tilemap, loc)| (*tilemap, loc.0.x, loc.0.y));
+    tile_chunks.dedup();
+
+    for (tilemap_entity, loc) in tile_chunks {
+        let Ok((tilemap, mut sand)) = tilemaps.get_mut(tilemap_entity) else {
+            continue;
+        };
+        sand.wake(loc.chunk(tilemap.dimensions()));
+    }
+}
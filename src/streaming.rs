@@ -0,0 +1,596 @@
+//! Unloading chunks far away from every [`StreamingAnchor`], and loading them again once an anchor returns.
+//!
+//! Add [`ChunkStreaming`] to a tilemap entity to enable streaming for it.
+//! If the tilemap also has a [`TilemapGenerator`], chunks near anchors that were never unloaded are created
+//! so they can be generated.
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    chunk::Chunk,
+    dimensions::TilemapDimensions,
+    events::{ChunkLoadedEvent, ChunkUnloadedEvent},
+    generation::{GeneratingChunk, TilemapGenerator},
+    multi_tile::{MultiTile, MultiTileMarker},
+    position::ChunkPos,
+    save::{ChunkSave, TilemapSave, TilemapSaveError, SAVE_VERSION},
+    tilemap::Tilemap,
+};
+
+/// Keeps the chunks around this entity's [`GlobalTransform`] loaded
+#[derive(Component, Default, Clone, Copy, Debug)]
+pub struct StreamingAnchor;
+
+/// Where unloaded chunks are kept until they are needed again
+pub trait ChunkStore: Send + Sync + 'static {
+    /// Called when a chunk is unloaded.
+    /// The chunk stays loaded if this returns an error.
+    fn save(
+        &mut self,
+        dimensions: TilemapDimensions,
+        chunk: ChunkSave,
+    ) -> Result<(), TilemapSaveError>;
+
+    /// Called when an anchor comes close to a chunk the store [contains](ChunkStore::contains).
+    /// Chunks that fail to load aren't asked for again, see [`ChunkStreaming::failed_chunks`].
+    fn load(&mut self, loc: ChunkPos) -> Result<Option<ChunkSave>, TilemapSaveError>;
+
+    /// Whether the chunk at the location, in chunks, can be loaded from this store
+    fn contains(&self, loc: ChunkPos) -> bool;
+
+    /// Every chunk in the store, without removing them, so they can be included in a [`TilemapSave`]
+    fn chunks(&self) -> Result<Vec<ChunkSave>, TilemapSaveError>;
+}
+
+/// Enables chunk streaming for a tilemap.
+///
+/// Multi tiles aren't streamed: unloading a chunk detaches its tiles from their multi tile,
+/// they are loaded again as plain tiles, and a multi tile is despawned once none of its tiles are loaded.
+#[derive(Component)]
+pub struct ChunkStreaming {
+    /// Chunks this many chunks or closer to an anchor are loaded
+    pub load_radius: i32,
+    /// Chunks further than this many chunks from every anchor are unloaded.
+    /// Keeping this larger than `load_radius` stops chunks on the edge from reloading every frame.
+    pub unload_radius: i32,
+    /// Unloaded chunks are dropped when there isn't a store
+    pub store: Option<Box<dyn ChunkStore>>,
+    /// Chunks that couldn't be loaded, with their save if it was read but couldn't be spawned
+    failed: HashMap<ChunkPos, Option<ChunkSave>>,
+}
+
+impl ChunkStreaming {
+    pub fn new(load_radius: i32, unload_radius: i32) -> Self {
+        assert!(unload_radius >= load_radius);
+
+        Self {
+            load_radius,
+            unload_radius,
+            store: None,
+            failed: HashMap::new(),
+        }
+    }
+
+    pub fn with_store(mut self, store: impl ChunkStore) -> Self {
+        self.store = Some(Box::new(store));
+        self
+    }
+
+    /// The chunks that couldn't be loaded from the store, and their save if it was read but couldn't be spawned.
+    /// They aren't loaded or generated again until they are [cleared](ChunkStreaming::clear_failed_chunk).
+    pub fn failed_chunks(&self) -> impl Iterator<Item = (ChunkPos, Option<&ChunkSave>)> {
+        self.failed.iter().map(|(loc, save)| (*loc, save.as_ref()))
+    }
+
+    /// Lets the chunk load again, returning its save if it was read but couldn't be spawned,
+    /// so it can be fixed and put back in the store
+    pub fn clear_failed_chunk(&mut self, loc: ChunkPos) -> Option<ChunkSave> {
+        self.failed.remove(&loc).flatten()
+    }
+}
+
+/// Keeps unloaded chunks in memory
+#[derive(Default)]
+pub struct MemoryChunkStore {
+    chunks: bevy::utils::HashMap<ChunkPos, ChunkSave>,
+}
+
+impl ChunkStore for MemoryChunkStore {
+    fn save(
+        &mut self,
+        _dimensions: TilemapDimensions,
+        chunk: ChunkSave,
+    ) -> Result<(), TilemapSaveError> {
+        self.chunks.insert(chunk.loc, chunk);
+        Ok(())
+    }
+
+    fn load(&mut self, loc: ChunkPos) -> Result<Option<ChunkSave>, TilemapSaveError> {
+        Ok(self.chunks.remove(&loc))
+    }
+
+    fn contains(&self, loc: ChunkPos) -> bool {
+        self.chunks.contains_key(&loc)
+    }
+
+    fn chunks(&self) -> Result<Vec<ChunkSave>, TilemapSaveError> {
+        Ok(self.chunks.values().cloned().collect())
+    }
+}
+
+/// Writes unloaded chunks to a directory, one binary save per chunk
+pub struct DirectoryChunkStore {
+    path: PathBuf,
+    saved: HashSet<ChunkPos>,
+}
+
+impl DirectoryChunkStore {
+    /// Creates the directory if it doesn't exist, chunks already saved in it can be loaded
+    pub fn new(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        fs::create_dir_all(&path)?;
+
+        let mut saved = HashSet::new();
+        for entry in fs::read_dir(&path)? {
+            if let Some(loc) = parse_chunk_file_name(&entry?.path()) {
+                saved.insert(loc);
+            }
+        }
+
+        Ok(Self { path, saved })
+    }
+
+    fn chunk_path(&self, loc: ChunkPos) -> PathBuf {
+        self.path.join(format!("{}_{}.chunk", loc.0.x, loc.0.y))
+    }
+
+    fn read(&self, loc: ChunkPos) -> Result<Option<ChunkSave>, TilemapSaveError> {
+        if !self.saved.contains(&loc) {
+            return Ok(None);
+        }
+
+        let save = TilemapSave::from_bytes(&fs::read(self.chunk_path(loc))?)?;
+        Ok(save.chunks.into_iter().find(|chunk| chunk.loc == loc))
+    }
+}
+
+impl ChunkStore for DirectoryChunkStore {
+    fn save(
+        &mut self,
+        dimensions: TilemapDimensions,
+        chunk: ChunkSave,
+    ) -> Result<(), TilemapSaveError> {
+        let loc = chunk.loc;
+        let save = TilemapSave {
+            version: SAVE_VERSION,
+            chunk_size: dimensions.chunk_size(),
+            tile_size: dimensions.tile_size(),
+            chunks: vec![chunk],
+            multi_tiles: vec![],
+        };
+
+        fs::write(self.chunk_path(loc), save.to_bytes()?)?;
+        self.saved.insert(loc);
+
+        Ok(())
+    }
+
+    fn load(&mut self, loc: ChunkPos) -> Result<Option<ChunkSave>, TilemapSaveError> {
+        self.read(loc)
+    }
+
+    fn contains(&self, loc: ChunkPos) -> bool {
+        self.saved.contains(&loc)
+    }
+
+    fn chunks(&self) -> Result<Vec<ChunkSave>, TilemapSaveError> {
+        let mut chunks = vec![];
+        for loc in &self.saved {
+            chunks.extend(self.read(*loc)?);
+        }
+
+        Ok(chunks)
+    }
+}
+
+fn parse_chunk_file_name(path: &Path) -> Option<ChunkPos> {
+    if path.extension()? != "chunk" {
+        return None;
+    }
+
+    let (x, y) = path.file_stem()?.to_str()?.split_once('_')?;
+    Some(ChunkPos::new(x.parse().ok()?, y.parse().ok()?))
+}
+
+pub fn chunk_streaming_system(world: &mut World) {
+    let anchors = world
+        .query_filtered::<&GlobalTransform, With<StreamingAnchor>>()
+        .iter(world)
+        .map(|transform| transform.translation())
+        .collect::<Vec<_>>();
+
+    let tilemaps = world
+        .query_filtered::<Entity, (With<Tilemap>, With<ChunkStreaming>)>()
+        .iter(world)
+        .collect::<Vec<_>>();
+
+    for tilemap in tilemaps {
+        stream_tilemap(world, tilemap, &anchors);
+    }
+}
+
+fn stream_tilemap(world: &mut World, tilemap_entity: Entity, anchors: &[Vec3]) {
+    let transform = world
+        .get::<GlobalTransform>(tilemap_entity)
+        .copied()
+        .unwrap_or_default();
+    let tilemap = world
+        .get::<Tilemap>(tilemap_entity)
+        .expect("Tilemap exists");
+    let streaming = world
+        .get::<ChunkStreaming>(tilemap_entity)
+        .expect("Streaming exists");
+    let dimensions = tilemap.dimensions();
+    let (load_radius, unload_radius) = (streaming.load_radius, streaming.unload_radius);
+
+    let anchor_chunks = anchors
+        .iter()
+        .map(|anchor| {
+            tilemap
+                .global_to_tile(&transform, anchor.truncate())
+                .chunk(dimensions)
+        })
+        .collect::<Vec<_>>();
+    let near_anchor = |loc: ChunkPos, radius: i32| {
+        anchor_chunks
+            .iter()
+            .any(|anchor| (loc - *anchor).abs().max_element() <= radius)
+    };
+
+    // Chunks with queued edits stay loaded until the edits are applied, and generating chunks until
+    // they are generated, otherwise they would be stored empty and never generated again
+    let unloading = tilemap
+        .chunks()
+        .filter(|(loc, _)| !near_anchor(*loc, unload_radius))
+        .filter(|(loc, _)| !tilemap.has_queued_edits_in_chunk(*loc))
+        .filter(|(_, chunk)| world.get::<GeneratingChunk>(*chunk).is_none())
+        .collect::<Vec<_>>();
+
+    let mut streaming = world
+        .get_mut::<ChunkStreaming>(tilemap_entity)
+        .expect("Streaming exists");
+    let mut store = streaming.store.take();
+    let mut failed = std::mem::take(&mut streaming.failed);
+
+    for (loc, chunk) in unloading {
+        if let Some(store) = &mut store {
+            let saved =
+                ChunkSave::capture(world, chunk, loc).and_then(|save| store.save(dimensions, save));
+            if let Err(error) = saved {
+                error!("Failed to save chunk {loc} while unloading it: {error}");
+                continue;
+            }
+        }
+
+        detach_multi_tiles(world, chunk);
+        world
+            .get_mut::<Tilemap>(tilemap_entity)
+            .expect("Tilemap exists")
+            .remove_chunk(loc);
+        world.entity_mut(chunk).despawn_recursive();
+
+        world.send_event(ChunkUnloadedEvent {
+            tilemap: tilemap_entity,
+            loc,
+        });
+    }
+
+    let generates = world.get::<TilemapGenerator>(tilemap_entity).is_some();
+
+    for anchor in &anchor_chunks {
+        for x in -load_radius..=load_radius {
+            for y in -load_radius..=load_radius {
+                let loc = *anchor + IVec2::new(x, y);
+
+                let mut tilemap = world
+                    .get_mut::<Tilemap>(tilemap_entity)
+                    .expect("Tilemap exists");
+                if tilemap.chunk_at(loc).is_some() || failed.contains_key(&loc) {
+                    continue;
+                }
+
+                let Some(store) = store.as_mut().filter(|store| store.contains(loc)) else {
+                    // The store doesn't have chunks that were never unloaded, or that were dropped on unload
+                    // because there is no store, so they are made fresh for the generator to fill.
+                    // Chunks whose save failed were never unloaded in the first place.
+                    if generates {
+                        tilemap.require_chunk(loc.origin(dimensions));
+                    }
+                    continue;
+                };
+
+                let save = match store.load(loc) {
+                    Ok(Some(save)) => save,
+                    Ok(None) => continue,
+                    Err(error) => {
+                        error!("Failed to load chunk {loc}: {error}");
+                        failed.insert(loc, None);
+                        continue;
+                    }
+                };
+
+                match save.spawn(world, tilemap_entity) {
+                    Ok(chunk) => world.send_event(ChunkLoadedEvent {
+                        tilemap: tilemap_entity,
+                        loc,
+                        chunk,
+                    }),
+                    Err(error) => {
+                        error!("Failed to spawn chunk {loc}: {error}");
+                        failed.insert(loc, Some(save));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut streaming = world
+        .get_mut::<ChunkStreaming>(tilemap_entity)
+        .expect("Streaming exists");
+    streaming.store = store;
+    streaming.failed = failed;
+}
+
+/// Removes the chunk's tiles from the multi tiles they are part of,
+/// despawning multi tiles that have no tiles left
+fn detach_multi_tiles(world: &mut World, chunk: Entity) {
+    let Some(chunk) = world.get::<Chunk>(chunk) else {
+        return;
+    };
+    let detached = chunk
+        .tiles()
+        .filter_map(|(_, tile)| Some((tile, world.get::<MultiTileMarker>(tile)?.entity)))
+        .collect::<Vec<_>>();
+
+    for (tile, multi_tile_entity) in detached {
+        let Some(mut multi_tile) = world.get_mut::<MultiTile>(multi_tile_entity) else {
+            continue;
+        };
+        multi_tile.entities.retain(|entity| *entity != tile);
+
+        if multi_tile.entities.is_empty() {
+            world.entity_mut(multi_tile_entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        chunk::Chunk,
+        generation::{ChunkGenerator, GeneratedChunk},
+        plugin::PixelPlugin,
+        position::{LocalTilePos, PixelPos, TilePos},
+        save::TileSave,
+        tile::Tile,
+        tilemap::TilemapBundle,
+    };
+
+    /// A tilemap streamed around a single anchor, which loads only the chunk it is in
+    fn app(store: MemoryChunkStore, tilemap: impl Bundle) -> (App, Entity, Entity) {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .add_plugins(PixelPlugin);
+        let tilemap = app
+            .world
+            .spawn((
+                TilemapBundle::default(),
+                ChunkStreaming::new(0, 1).with_store(store),
+                tilemap,
+            ))
+            .id();
+        let anchor = app
+            .world
+            .spawn((StreamingAnchor, GlobalTransform::default()))
+            .id();
+
+        (app, tilemap, anchor)
+    }
+
+    /// Moves the anchor to the chunk `x` chunks right of the origin
+    fn move_anchor(app: &mut App, anchor: Entity, x: i32) {
+        let dimensions = TilemapDimensions::default();
+        *app.world.get_mut::<GlobalTransform>(anchor).unwrap() =
+            GlobalTransform::from_xyz((x * dimensions.chunk_size() as i32) as f32, 0.0, 0.0);
+    }
+
+    fn chunk_tiles(app: &App, tilemap: Entity, loc: ChunkPos) -> Option<usize> {
+        let chunk = app.world.get::<Tilemap>(tilemap)?.chunk_at(loc)?;
+        Some(app.world.get::<Chunk>(chunk)?.tiles().count())
+    }
+
+    /// Places a single tile in every chunk, once `ready` is set
+    struct BlockingGenerator {
+        ready: Arc<AtomicBool>,
+    }
+
+    impl ChunkGenerator for BlockingGenerator {
+        fn generate(
+            &self,
+            _chunk: ChunkPos,
+            _seed: u64,
+            dimensions: TilemapDimensions,
+        ) -> GeneratedChunk {
+            while !self.ready.load(Ordering::Acquire) {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            let mut generated = GeneratedChunk::new(dimensions);
+            generated.set_tile(
+                LocalTilePos::ZERO,
+                Tile::from_color(Color::RED, dimensions.tile_size()),
+            );
+            generated
+        }
+    }
+
+    fn update_until(app: &mut App, mut done: impl FnMut(&App) -> bool) {
+        for _ in 0..1000 {
+            if done(app) {
+                return;
+            }
+            app.update();
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("Timed out");
+    }
+
+    #[test]
+    fn generating_chunks_stay_loaded() {
+        let ready = Arc::new(AtomicBool::new(false));
+        let generator = BlockingGenerator {
+            ready: ready.clone(),
+        };
+        let (mut app, tilemap, anchor) = app(
+            MemoryChunkStore::default(),
+            TilemapGenerator::new(generator, 0),
+        );
+
+        app.update();
+        app.update();
+        assert_eq!(chunk_tiles(&app, tilemap, ChunkPos::ZERO), Some(0));
+
+        // The origin chunk is out of range, but still being generated
+        move_anchor(&mut app, anchor, 5);
+        app.update();
+        app.update();
+        assert_eq!(chunk_tiles(&app, tilemap, ChunkPos::ZERO), Some(0));
+
+        ready.store(true, Ordering::Release);
+        update_until(&mut app, |app| {
+            chunk_tiles(app, tilemap, ChunkPos::ZERO).is_none()
+        });
+
+        move_anchor(&mut app, anchor, 0);
+        update_until(&mut app, |app| {
+            chunk_tiles(app, tilemap, ChunkPos::ZERO).is_some()
+        });
+        assert_eq!(chunk_tiles(&app, tilemap, ChunkPos::ZERO), Some(1));
+    }
+
+    #[test]
+    fn unload_and_reload() {
+        let (mut app, tilemap, anchor) = app(MemoryChunkStore::default(), ());
+        app.world.run_system_once(
+            move |mut commands: Commands, mut tilemaps: Query<&mut Tilemap>| {
+                let mut tilemap = tilemaps.get_mut(tilemap).unwrap();
+                let tile = Tile::from_color(Color::BLUE, tilemap.dimensions().tile_size());
+                tilemap.set_tile(&mut commands, TilePos::new(1, 2), tile, ());
+            },
+        );
+        app.update();
+        app.update();
+        assert_eq!(chunk_tiles(&app, tilemap, ChunkPos::ZERO), Some(1));
+
+        move_anchor(&mut app, anchor, 5);
+        app.update();
+        assert_eq!(chunk_tiles(&app, tilemap, ChunkPos::ZERO), None);
+        assert_eq!(app.world.query::<&Tile>().iter(&app.world).count(), 0);
+
+        move_anchor(&mut app, anchor, 0);
+        app.update();
+        assert_eq!(chunk_tiles(&app, tilemap, ChunkPos::ZERO), Some(1));
+        let mut tiles = app.world.query::<&Tile>();
+        let tile = tiles.single(&app.world);
+        assert_eq!(
+            tile.get_pixel(PixelPos::ZERO),
+            Some(Color::rgba_u8(0, 0, 255, 255))
+        );
+    }
+
+    #[test]
+    fn failed_chunks_are_not_retried() {
+        let loc = ChunkPos::new(5, 0);
+        let broken = ChunkSave {
+            loc,
+            tiles: vec![TileSave {
+                loc: LocalTilePos::ZERO,
+                pixels: vec![0; 3],
+                materials: vec![],
+                multi_tile: None,
+                components: vec![],
+            }],
+        };
+        let mut store = MemoryChunkStore::default();
+        store
+            .save(TilemapDimensions::default(), broken.clone())
+            .unwrap();
+        let (mut app, tilemap, anchor) = app(store, ());
+
+        move_anchor(&mut app, anchor, 5);
+        app.update();
+        app.update();
+        assert_eq!(chunk_tiles(&app, tilemap, loc), None);
+
+        let mut streaming = app.world.get_mut::<ChunkStreaming>(tilemap).unwrap();
+        assert_eq!(
+            streaming.failed_chunks().collect::<Vec<_>>(),
+            vec![(loc, Some(&broken))]
+        );
+        assert_eq!(streaming.clear_failed_chunk(loc), Some(broken));
+    }
+
+    #[test]
+    fn saves_include_unloaded_chunks() {
+        let (mut app, tilemap, anchor) = app(MemoryChunkStore::default(), ());
+        app.world.run_system_once(
+            move |mut commands: Commands, mut tilemaps: Query<&mut Tilemap>| {
+                let mut tilemap = tilemaps.get_mut(tilemap).unwrap();
+                let tile_size = tilemap.dimensions().tile_size();
+                tilemap.set_tile(
+                    &mut commands,
+                    TilePos::new(1, 2),
+                    Tile::from_color(Color::BLUE, tile_size),
+                    (),
+                );
+            },
+        );
+        app.update();
+        app.update();
+
+        move_anchor(&mut app, anchor, 5);
+        app.update();
+        app.world
+            .get_mut::<Tilemap>(tilemap)
+            .unwrap()
+            .require_chunk(TilePos::new(80, 0));
+        app.update();
+        app.update();
+
+        let save = TilemapSave::capture(&app.world, tilemap).unwrap();
+        let chunks = save
+            .chunks
+            .iter()
+            .map(|chunk| (chunk.loc, chunk.tiles.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(chunks, vec![(ChunkPos::ZERO, 1), (ChunkPos::new(5, 0), 0)]);
+    }
+}
@@ -1,12 +1,23 @@
-use std::ops::Range;
+use std::{ops::Range, sync::Arc};
 
-use bevy::prelude::{Bundle, Color, Component, IVec2, Image, Transform};
+use bevy::prelude::{Bundle, Color, Component, Image, Transform};
 
-use crate::TILE_SIZE;
+use crate::{
+    bitset::BitSet,
+    dimensions::TilemapDimensions,
+    material::{MaterialId, MaterialRegistry},
+    position::{LocalTilePos, PixelPos, TilePos},
+    TILE_SIZE,
+};
 
 #[derive(Component)]
 pub struct DeletingTile;
 
+/// The location of a tile inside of its parent chunk.
+/// Inserted when the tile is placed in a [`Chunk`](crate::chunk::Chunk).
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkTileLocation(pub LocalTilePos);
+
 #[derive(Bundle)]
 pub struct TileBundle {
     transform: Transform,
@@ -14,85 +25,232 @@ pub struct TileBundle {
 }
 
 impl TileBundle {
-    pub fn new(tile: Tile, location: IVec2) -> Self {
+    pub fn new(tile: Tile, location: TilePos) -> Self {
         Self {
             tile,
-            transform: Transform::from_xyz(location.x as f32, location.y as f32, 0.0),
+            transform: Transform::from_xyz(location.0.x as f32, location.0.y as f32, 0.0),
         }
     }
 }
 
-#[derive(Component, Debug, Clone, PartialEq)]
+/// The pixels of a tile.
+/// Clones of a tile share their pixels until one of them is changed, see [`Tile::shares_pixels`].
+#[derive(Component, Debug)]
 pub struct Tile {
-    pub(crate) pixels: [[Color; TILE_SIZE]; TILE_SIZE],
+    size: usize,
+    data: Arc<TileData>,
+    /// Pixels changed through `set_pixel` since the owning chunk last redrew this tile.
+    /// `None` until the chunk first drew it, so new, cloned and replaced tiles are redrawn whole.
+    changed_pixels: Option<BitSet>,
 }
 
-impl Tile {
-    pub fn from_color(color: Color) -> Self {
+#[derive(Debug, Clone, PartialEq)]
+struct TileData {
+    /// Row major, `pixels[y * size + x]`
+    pixels: Vec<Color>,
+    /// The material of every pixel, in the same order as `pixels`
+    materials: Vec<MaterialId>,
+}
+
+impl Clone for Tile {
+    fn clone(&self) -> Self {
         Self {
-            pixels: [[color; TILE_SIZE]; TILE_SIZE],
+            size: self.size,
+            data: self.data.clone(),
+            changed_pixels: None,
         }
     }
+}
+
+impl PartialEq for Tile {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size && (self.shares_pixels(other) || self.data == other.data)
+    }
+}
+
+impl Tile {
+    /// Creates a tile of `size` by `size` pixels filled with `color`.
+    /// Tiles have to be the tile size of the tilemap they are placed in, see [`TilemapDimensions`].
+    pub fn from_color(color: Color, size: usize) -> Self {
+        Self::from_pixel_vec(size, vec![color; size * size])
+    }
 
     pub fn from_pixels(pixels: [[Color; TILE_SIZE]; TILE_SIZE]) -> Self {
-        Self { pixels }
+        Self::from_pixel_vec(TILE_SIZE, pixels.into_iter().flatten().collect())
+    }
+
+    /// Creates a tile from a row major list of pixels, `size` pixels wide.
+    pub fn from_pixel_vec(size: usize, pixels: Vec<Color>) -> Self {
+        Self::from_pixel_vec_with_materials(size, pixels, vec![MaterialId::NONE; size * size])
     }
 
+    /// Creates a tile from row major lists of pixels and their materials, `size` pixels wide.
+    pub fn from_pixel_vec_with_materials(
+        size: usize,
+        pixels: Vec<Color>,
+        materials: Vec<MaterialId>,
+    ) -> Self {
+        assert_eq!(pixels.len(), size * size);
+        assert_eq!(materials.len(), size * size);
+
+        Self {
+            size,
+            data: Arc::new(TileData { pixels, materials }),
+            changed_pixels: None,
+        }
+    }
+
+    /// Creates a tile at `loc` made entirely of `material`, with colors derived from the material
+    pub fn from_material(
+        material: MaterialId,
+        registry: &MaterialRegistry,
+        loc: TilePos,
+        dimensions: TilemapDimensions,
+    ) -> Self {
+        let size = dimensions.tile_size();
+        let mut tile = Self::from_color(Color::NONE, size);
+
+        for y in 0..size as i32 {
+            for x in 0..size as i32 {
+                let pixel = PixelPos::new(x, y);
+                let color = registry.pixel_color(material, loc.world_pixel(pixel, dimensions));
+                tile.set_material(pixel, material, color);
+            }
+        }
+
+        tile
+    }
+
+    /// Creates a tile by copying a square region of the image.
+    /// The size of the tile is the length of the ranges.
     pub fn from_image(image: &Image, pixel_range: (Range<usize>, Range<usize>)) -> Self {
-        let mut colors = [[Color::NONE; TILE_SIZE]; TILE_SIZE];
+        let size = pixel_range.0.len();
+        assert_eq!(pixel_range.1.len(), size);
 
-        assert_eq!(pixel_range.0.len(), TILE_SIZE);
-        assert_eq!(pixel_range.1.len(), TILE_SIZE);
+        let mut colors = vec![Color::NONE; size * size];
 
         for x in pixel_range.0.clone() {
             for y in pixel_range.1.clone() {
                 let pixel_index = y * image.size().x as usize * 4 + x * 4;
 
-                colors[y - pixel_range.1.start][x - pixel_range.0.start] = Color::rgba_u8(
-                    image.data[pixel_index],
-                    image.data[pixel_index + 1],
-                    image.data[pixel_index + 2],
-                    image.data[pixel_index + 3],
-                );
+                colors[(y - pixel_range.1.start) * size + (x - pixel_range.0.start)] =
+                    Color::rgba_u8(
+                        image.data[pixel_index],
+                        image.data[pixel_index + 1],
+                        image.data[pixel_index + 2],
+                        image.data[pixel_index + 3],
+                    );
             }
         }
 
-        Self { pixels: colors }
+        Self::from_pixel_vec(size, colors)
+    }
+
+    /// The width and height of the tile in pixels
+    pub fn size(&self) -> usize {
+        self.size
     }
 
-    pub fn set_pixel(&mut self, loc: IVec2, color: Color) {
-        if !verify_pixel_loc(loc) {
+    /// Replaces the pixels with the ones of `tile`, sharing them, and redraws the whole tile.
+    /// Pixels recorded as changed on either tile are dropped.
+    pub fn replace(&mut self, tile: &Tile) {
+        *self = tile.clone();
+    }
+
+    /// Sets the color of a pixel, which is then no longer made of a material
+    pub fn set_pixel(&mut self, loc: PixelPos, color: Color) {
+        self.set_material(loc, MaterialId::NONE, color)
+    }
+
+    /// Sets both the material and the color of a pixel
+    pub fn set_material(&mut self, loc: PixelPos, material: MaterialId, color: Color) {
+        let Some(index) = self.pixel_index(loc) else {
             return;
+        };
+
+        let data = Arc::make_mut(&mut self.data);
+        data.pixels[index] = color;
+        data.materials[index] = material;
+        if let Some(changed_pixels) = &mut self.changed_pixels {
+            changed_pixels.insert(index);
         }
+    }
 
-        self.pixels[loc.y as usize][loc.x as usize] = color
+    pub fn get_material(&self, loc: PixelPos) -> Option<MaterialId> {
+        Some(self.data.materials[self.pixel_index(loc)?])
     }
 
-    pub fn get_pixel(&self, loc: IVec2) -> Option<Color> {
-        if !verify_pixel_loc(loc) {
-            return None;
-        }
+    /// Every pixel, row major
+    pub fn pixels(&self) -> &[Color] {
+        &self.data.pixels
+    }
+
+    /// The material of every pixel, row major
+    pub fn materials(&self) -> &[MaterialId] {
+        &self.data.materials
+    }
+
+    /// Whether both tiles still use the same pixels, meaning neither was changed since one was cloned from the other
+    pub fn shares_pixels(&self, other: &Tile) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
 
-        Some(self.pixels[loc.y as usize][loc.x as usize])
+    pub fn get_pixel(&self, loc: PixelPos) -> Option<Color> {
+        Some(self.data.pixels[self.pixel_index(loc)?])
+    }
+
+    /// Whether the pixel is solid, see [`MaterialRegistry::is_solid`]
+    pub fn is_solid(
+        &self,
+        loc: PixelPos,
+        alpha_threshold: f32,
+        materials: &MaterialRegistry,
+    ) -> bool {
+        let Some(index) = self.pixel_index(loc) else {
+            return false;
+        };
+
+        materials.is_solid(
+            self.data.pixels[index],
+            self.data.materials[index],
+            alpha_threshold,
+        )
     }
 
     pub fn pixel_count(&self) -> usize {
-        let mut pixel_count = 0;
-        for x in 0..TILE_SIZE {
-            for y in 0..TILE_SIZE {
-                if self.pixels[y][x].a() > 0.0 {
-                    pixel_count += 1;
-                }
-            }
+        self.data
+            .pixels
+            .iter()
+            .filter(|pixel| pixel.a() > 0.0)
+            .count()
+    }
+
+    /// Takes the pixels changed by `set_pixel`, and starts recording them if this is the first draw.
+    /// Returns `None` if no individual pixels were recorded, meaning the whole tile should be redrawn.
+    pub(crate) fn take_changed_pixels(&mut self) -> Option<Vec<PixelPos>> {
+        let size = self.size;
+        let changed_pixels = self
+            .changed_pixels
+            .get_or_insert_with(|| BitSet::new(size * size));
+        if changed_pixels.is_empty() {
+            return None;
         }
 
-        pixel_count
+        let pixels = changed_pixels
+            .iter()
+            .map(|index| PixelPos::new((index % size) as i32, (index / size) as i32))
+            .collect();
+        changed_pixels.clear();
+
+        Some(pixels)
     }
-}
 
-fn verify_pixel_loc(loc: IVec2) -> bool {
-    if loc.x < 0 || loc.x >= TILE_SIZE as i32 || loc.y < 0 || loc.y >= TILE_SIZE as i32 {
-        return false;
+    fn pixel_index(&self, loc: PixelPos) -> Option<usize> {
+        let size = self.size as i32;
+        if loc.0.x < 0 || loc.0.x >= size || loc.0.y < 0 || loc.0.y >= size {
+            return None;
+        }
+
+        Some(loc.0.y as usize * self.size + loc.0.x as usize)
     }
-    true
 }
@@ -0,0 +1,78 @@
+//! Registered tile types, whose placed tiles share their pixels.
+//!
+//! Tiles placed from a [`TileRegistry`] get a [`TileKind`] component, and use the pixels of the registered
+//! tile until one of their pixels is changed, which gives that tile its own copy.
+//! Loaded and streamed in tiles that still match their registered tile share its pixels again.
+use bevy::prelude::*;
+
+use crate::tile::Tile;
+
+/// The registered type a tile was placed as, an index into the [`TileRegistry`]
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[reflect(Component)]
+pub struct TileKind(pub u32);
+
+/// A tile type in the [`TileRegistry`]
+#[derive(Clone, Debug)]
+pub struct TileType {
+    pub name: String,
+    tile: Tile,
+}
+
+impl TileType {
+    /// The tile every tile of this type starts out as
+    pub fn tile(&self) -> &Tile {
+        &self.tile
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default)]
+pub struct TileRegistry {
+    types: Vec<TileType>,
+}
+
+impl TileRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a tile type, returning its kind.
+    /// Kinds are handed out in order, so registering the same types in the same order gives the same kinds.
+    pub fn register(&mut self, name: impl Into<String>, tile: Tile) -> TileKind {
+        self.types.push(TileType {
+            name: name.into(),
+            tile,
+        });
+        TileKind(self.types.len() as u32 - 1)
+    }
+
+    pub fn get(&self, kind: TileKind) -> Option<&TileType> {
+        self.types.get(kind.0 as usize)
+    }
+
+    /// Finds a tile type by name
+    pub fn find(&self, name: &str) -> Option<TileKind> {
+        self.types
+            .iter()
+            .position(|tile_type| tile_type.name == name)
+            .map(|index| TileKind(index as u32))
+    }
+
+    /// A new tile of the kind, sharing its pixels with the registered tile
+    pub fn tile(&self, kind: TileKind) -> Option<Tile> {
+        self.get(kind).map(|tile_type| tile_type.tile.clone())
+    }
+
+    /// Whether the tile still uses the pixels of its registered type
+    pub fn is_unmodified(&self, kind: TileKind, tile: &Tile) -> bool {
+        self.get(kind)
+            .is_some_and(|tile_type| tile_type.tile.shares_pixels(tile))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TileKind, &TileType)> {
+        self.types
+            .iter()
+            .enumerate()
+            .map(|(index, tile_type)| (TileKind(index as u32), tile_type))
+    }
+}
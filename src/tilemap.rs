@@ -1,32 +1,76 @@
 #![allow(clippy::type_complexity)]
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use bevy::{prelude::*, transform::TransformBundle};
+use thiserror::Error;
 
 use crate::{
+    animation::AnimatedTile,
+    autotile::{AutoTile, AutoTileSetId, AutoTileSets, Neighbors},
     chunk::{Chunk, ChunkBundle},
+    destruction::Destructible,
+    dimensions::TilemapDimensions,
+    events::{
+        ChunkCreatedEvent, PixelChangedEvent, TileDeletedEvent, TileSetEvent, TilemapEventWriters,
+    },
+    image_tile::TileImageSource,
+    material::{MaterialId, MaterialRegistry},
+    overlap::{overlap, overlaps, sweep, PixelOverlap, PixelShape, PixelSweepHit},
+    position::{ChunkPos, PixelPos, TilePos, WorldPixelPos},
+    raycast::{raycast, PixelRay, PixelRayHit},
     tile::{Tile, TileBundle},
-    util::{chunk_from_location, tile_from_location},
+    tile_kind::{TileKind, TileRegistry},
+    util::{
+        tile_to_world_unit, world_pixel_to_world_unit, world_unit_to_pixel,
+        world_unit_to_pixel_space, world_unit_to_tile, world_unit_to_world_pixel,
+    },
 };
 
 #[derive(Clone, Debug)]
 pub enum TileEvent {
-    MakeChunk(IVec2),
+    MakeChunk(ChunkPos),
     SetTile {
-        loc: IVec2,
+        loc: TilePos,
         entity: Entity,
     },
     DeleteTile {
-        loc: IVec2,
+        loc: TilePos,
         mark: bool,
     },
-    SetPixel {
-        loc: IVec2,
-        pixel: IVec2,
-        color: Color,
+    SetPixels {
+        loc: TilePos,
+        /// Pixels set by color alone have [`MaterialId::NONE`]
+        pixels: Vec<(PixelPos, Color, MaterialId)>,
+        /// Create a transparent tile if there isn't one at the location
+        create: bool,
+        /// Delete the tile if it has no visible pixels left afterwards
+        delete_empty: bool,
     },
 }
 
+impl TileEvent {
+    /// The chunk the event affects
+    pub fn chunk(&self, dimensions: TilemapDimensions) -> ChunkPos {
+        match self {
+            TileEvent::MakeChunk(loc) => *loc,
+            TileEvent::SetTile { loc, .. } => loc.chunk(dimensions),
+            TileEvent::DeleteTile { loc, .. } => loc.chunk(dimensions),
+            TileEvent::SetPixels { loc, .. } => loc.chunk(dimensions),
+        }
+    }
+}
+
+/// Why [`Tilemap::try_set_tile`] didn't place a tile
+#[derive(Error, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetTileError {
+    #[error("the location already has tile {0:?}")]
+    Occupied(Entity),
+    #[error("a {found} pixel tile can't be placed in a tilemap with {expected} pixel tiles")]
+    WrongSize { expected: usize, found: usize },
+    #[error("{0:?} is not a tilemap")]
+    NotATilemap(Entity),
+}
+
 #[derive(Bundle, Default)]
 pub struct TilemapBundle {
     tilemap: Tilemap,
@@ -34,199 +78,848 @@ pub struct TilemapBundle {
     visibility: VisibilityBundle,
 }
 
+impl TilemapBundle {
+    pub fn new(tilemap: Tilemap) -> Self {
+        Self {
+            tilemap,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Component, Default)]
 pub struct Tilemap {
-    chunks: HashMap<IVec2, Entity>,
-    pub(crate) tasks: VecDeque<TileEvent>,
+    dimensions: TilemapDimensions,
+    chunks: HashMap<ChunkPos, Entity>,
+    tasks: VecDeque<TileEvent>,
+    /// The chunks affected by `tasks`
+    queued_chunks: HashSet<ChunkPos>,
+    /// Queued sets (`Some`) and deletes (`None`) that haven't been applied to their chunk yet
+    pending: HashMap<TilePos, Option<Entity>>,
 }
 
 impl Tilemap {
     pub fn new() -> Self {
+        Self::with_dimensions(TilemapDimensions::default())
+    }
+
+    /// Creates a tilemap whose chunks and tiles use the given sizes
+    pub fn with_dimensions(dimensions: TilemapDimensions) -> Self {
         Self {
+            dimensions,
             chunks: HashMap::new(),
             tasks: VecDeque::new(),
+            queued_chunks: HashSet::new(),
+            pending: HashMap::new(),
         }
     }
 
-    pub fn require_chunk(&mut self, loc: IVec2) {
-        let chunk = chunk_from_location(loc);
+    pub fn dimensions(&self) -> TilemapDimensions {
+        self.dimensions
+    }
+
+    /// Converts a coordinate in the tilemap's local space to a tile location on this tilemap
+    pub fn world_unit_to_tile(&self, loc: Vec2) -> TilePos {
+        world_unit_to_tile(loc, self.dimensions)
+    }
 
-        if !self.chunks.contains_key(&IVec2::new(chunk.x, chunk.y)) {
-            self.tasks
-                .push_front(TileEvent::MakeChunk(IVec2::new(chunk.x, chunk.y)));
+    /// Converts a coordinate in the tilemap's local space to a tile & pixel location on this tilemap
+    pub fn world_unit_to_pixel(&self, loc: Vec2) -> (TilePos, PixelPos) {
+        world_unit_to_pixel(loc, self.dimensions)
+    }
+
+    /// Converts a world coordinate to the tile under it, given the tilemap's transform
+    pub fn global_to_tile(&self, transform: &GlobalTransform, loc: Vec2) -> TilePos {
+        world_unit_to_tile(global_to_local(transform, loc), self.dimensions)
+    }
+
+    /// Converts a world coordinate to the tile & pixel under it, given the tilemap's transform
+    pub fn global_to_pixel(&self, transform: &GlobalTransform, loc: Vec2) -> (TilePos, PixelPos) {
+        world_unit_to_pixel(global_to_local(transform, loc), self.dimensions)
+    }
+
+    /// Converts a world coordinate to the world pixel under it, given the tilemap's transform
+    pub fn global_to_world_pixel(&self, transform: &GlobalTransform, loc: Vec2) -> WorldPixelPos {
+        world_unit_to_world_pixel(global_to_local(transform, loc), self.dimensions)
+    }
+
+    /// Converts a world coordinate to world pixel space, given the tilemap's transform.
+    /// Used to aim a [`PixelRay`] from world coordinates.
+    pub fn global_to_pixel_space(&self, transform: &GlobalTransform, loc: Vec2) -> Vec2 {
+        world_unit_to_pixel_space(global_to_local(transform, loc), self.dimensions)
+    }
+
+    /// The world coordinate of the center of a tile, given the tilemap's transform
+    pub fn tile_to_global(&self, transform: &GlobalTransform, loc: TilePos) -> Vec3 {
+        transform.transform_point(tile_to_world_unit(loc, self.dimensions).extend(0.0))
+    }
+
+    /// The world coordinate of the center of a pixel, given the tilemap's transform
+    pub fn pixel_to_global(
+        &self,
+        transform: &GlobalTransform,
+        loc: TilePos,
+        pixel: PixelPos,
+    ) -> Vec3 {
+        self.world_pixel_to_global(transform, loc.world_pixel(pixel, self.dimensions))
+    }
+
+    /// The world coordinate of the center of a world pixel, given the tilemap's transform
+    pub fn world_pixel_to_global(
+        &self,
+        transform: &GlobalTransform,
+        world_pixel: WorldPixelPos,
+    ) -> Vec3 {
+        transform
+            .transform_point(world_pixel_to_world_unit(world_pixel, self.dimensions).extend(0.0))
+    }
+
+    /// Queues creating the chunk the tile is in, if it doesn't exist yet
+    pub fn require_chunk(&mut self, loc: TilePos) {
+        if !self.has_chunk(loc) {
+            let chunk = loc.chunk(self.dimensions);
+            self.queued_chunks.insert(chunk);
+            self.tasks.push_front(TileEvent::MakeChunk(chunk));
         }
     }
 
+    /// Places a tile, returning its entity.
+    /// Tiles that aren't the tilemap's tile size are skipped with a warning, returning `None`.
     pub fn set_tile(
         &mut self,
         commands: &mut Commands,
-        loc: IVec2,
+        loc: TilePos,
         tile: Tile,
         additional_components: impl Bundle,
-    ) -> Entity {
+    ) -> Option<Entity> {
+        if let Err(error) = self.check_tile_size(&tile) {
+            warn!("Skipped placing a tile at {loc}: {error}");
+            return None;
+        }
+
         self.require_chunk(loc);
 
         let entity = commands
             .spawn((TileBundle::new(tile, loc), additional_components))
             .id();
 
-        self.tasks.push_back(TileEvent::SetTile { loc, entity });
+        self.push_task(TileEvent::SetTile { loc, entity });
+        self.pending.insert(loc, Some(entity));
+
+        Some(entity)
+    }
+
+    /// Places a tile copied from a region of an image, which is copied again whenever the image changes.
+    /// The tile is transparent until the image is loaded.
+    /// Returns `None` if the region isn't a square of the tilemap's tile size, or doesn't fit in the image.
+    pub fn set_image_tile(
+        &mut self,
+        commands: &mut Commands,
+        loc: TilePos,
+        mut source: TileImageSource,
+        images: &Assets<Image>,
+        additional_components: impl Bundle,
+    ) -> Option<Entity> {
+        let tile = source.tile(images)?;
+        self.set_tile(commands, loc, tile, (source, additional_components))
+    }
 
-        entity
+    /// Places a tile that cycles through the animation's frames.
+    /// Returns `None` if the frames aren't the tilemap's tile size.
+    pub fn set_animated_tile(
+        &mut self,
+        commands: &mut Commands,
+        loc: TilePos,
+        animation: AnimatedTile,
+        additional_components: impl Bundle,
+    ) -> Option<Entity> {
+        let frame = animation.frame_at(animation.phase as f64);
+        let tile = animation.frames().get(frame)?.tile.clone();
+        self.set_tile(commands, loc, tile, (animation, additional_components))
     }
 
+    /// Places a tile that picks its variant from the auto tile set by its neighbors.
+    /// The tile starts out as the variant without neighbors, and returns `None` if the set has no such variant.
+    pub fn set_auto_tile(
+        &mut self,
+        commands: &mut Commands,
+        loc: TilePos,
+        set: AutoTileSetId,
+        sets: &AutoTileSets,
+        additional_components: impl Bundle,
+    ) -> Option<Entity> {
+        let tile = sets.get(set)?.variant(Neighbors::default())?.clone();
+        self.set_tile(
+            commands,
+            loc,
+            tile,
+            (AutoTile::new(set), additional_components),
+        )
+    }
+
+    /// Places a tile of a registered kind, sharing its pixels with the registered tile.
+    /// Returns `None` if the kind isn't registered.
+    pub fn set_tile_kind(
+        &mut self,
+        commands: &mut Commands,
+        loc: TilePos,
+        kind: TileKind,
+        registry: &TileRegistry,
+        additional_components: impl Bundle,
+    ) -> Option<Entity> {
+        let tile = registry.tile(kind)?;
+        self.set_tile(commands, loc, tile, (kind, additional_components))
+    }
+
+    /// Places a tile only if the location is empty, including queued sets and deletes
     pub fn try_set_tile(
         &mut self,
         commands: &mut Commands,
         chunks: &Query<&Chunk>,
-        loc: IVec2,
+        loc: TilePos,
         tile: Tile,
         additional_components: impl Bundle,
-    ) -> Option<Entity> {
-        if self.get_tile(loc, chunks).is_some() {
-            return None;
+    ) -> Result<Entity, SetTileError> {
+        if let Some(existing) = self.get_tile(loc, chunks) {
+            return Err(SetTileError::Occupied(existing));
+        }
+        self.check_tile_size(&tile)?;
+
+        Ok(self
+            .set_tile(commands, loc, tile, additional_components)
+            .expect("The tile size was checked"))
+    }
+
+    fn check_tile_size(&self, tile: &Tile) -> Result<(), SetTileError> {
+        if tile.size() != self.dimensions.tile_size() {
+            return Err(SetTileError::WrongSize {
+                expected: self.dimensions.tile_size(),
+                found: tile.size(),
+            });
         }
 
-        Some(self.set_tile(commands, loc, tile, additional_components))
+        Ok(())
     }
 
-    pub fn set_pixel(&mut self, loc: IVec2, pixel: IVec2, color: Color) {
+    pub fn set_pixel(&mut self, loc: TilePos, pixel: PixelPos, color: Color) {
         if !self.has_chunk(loc) {
             return;
         }
 
-        self.tasks
-            .push_back(TileEvent::SetPixel { loc, pixel, color })
+        self.push_task(TileEvent::SetPixels {
+            loc,
+            pixels: vec![(pixel, color, MaterialId::NONE)],
+            create: false,
+            delete_empty: false,
+        })
     }
 
-    pub fn delete_tile(&mut self, loc: IVec2) {
-        if !self.has_chunk(loc) {
+    /// Sets a pixel in world pixel coordinates.
+    /// Does nothing if there isn't a tile at the pixel.
+    pub fn set_world_pixel(&mut self, world_pixel: WorldPixelPos, color: Color) {
+        let (loc, pixel) = world_pixel.split(self.dimensions);
+        self.set_pixel(loc, pixel, color)
+    }
+
+    /// Sets a pixel in world pixel coordinates.
+    /// Creates a transparent tile, and its chunk, if there isn't a tile at the pixel.
+    pub fn set_world_pixel_or_create(&mut self, world_pixel: WorldPixelPos, color: Color) {
+        let (loc, pixel) = world_pixel.split(self.dimensions);
+        self.require_chunk(loc);
+
+        self.push_task(TileEvent::SetPixels {
+            loc,
+            pixels: vec![(pixel, color, MaterialId::NONE)],
+            create: true,
+            delete_empty: false,
+        })
+    }
+
+    /// Makes a pixel in world pixel coordinates transparent, keeping the tile
+    pub fn clear_world_pixel(&mut self, world_pixel: WorldPixelPos) {
+        self.set_world_pixel(world_pixel, Color::NONE)
+    }
+
+    /// Makes every pixel of the shape transparent, deleting tiles with no visible pixels left
+    pub fn carve(&mut self, shape: &PixelShape) {
+        let pixels = shape
+            .pixels()
+            .into_iter()
+            .map(|pixel| (pixel, Color::NONE, MaterialId::NONE));
+        self.set_world_pixels(pixels, false, true)
+    }
+
+    /// Makes every pixel within `radius` of `center` transparent, see [`Tilemap::carve`]
+    pub fn carve_circle(&mut self, center: WorldPixelPos, radius: u32) {
+        self.carve(&PixelShape::circle(center, radius))
+    }
+
+    /// Makes every pixel inside of the polygon transparent, see [`Tilemap::carve`].
+    /// The points are in world pixel space.
+    pub fn carve_polygon(&mut self, points: &[Vec2]) {
+        self.carve(&PixelShape::polygon(points.to_vec()))
+    }
+
+    /// Sets every pixel of the shape to `color`, creating tiles where there aren't any
+    pub fn fill(&mut self, shape: &PixelShape, color: Color) {
+        let pixels = shape
+            .pixels()
+            .into_iter()
+            .map(|pixel| (pixel, color, MaterialId::NONE));
+        self.set_world_pixels(pixels, true, false)
+    }
+
+    /// Sets every pixel within `radius` of `center` to `color`, see [`Tilemap::fill`]
+    pub fn fill_circle(&mut self, center: WorldPixelPos, radius: u32, color: Color) {
+        self.fill(&PixelShape::circle(center, radius), color)
+    }
+
+    /// Sets a pixel in world pixel coordinates to `material`, with a color derived from the material.
+    /// Creates a transparent tile, and its chunk, if there isn't a tile at the pixel.
+    pub fn set_world_material(
+        &mut self,
+        world_pixel: WorldPixelPos,
+        material: MaterialId,
+        registry: &MaterialRegistry,
+    ) {
+        let color = registry.pixel_color(material, world_pixel);
+        self.set_world_pixels([(world_pixel, color, material)], true, false)
+    }
+
+    /// Sets every pixel of the shape to `material`, see [`Tilemap::set_world_material`]
+    pub fn fill_material(
+        &mut self,
+        shape: &PixelShape,
+        material: MaterialId,
+        registry: &MaterialRegistry,
+    ) {
+        let pixels = shape.pixels().into_iter().map(|pixel| {
+            let color = registry.pixel_color(material, pixel);
+            (pixel, color, material)
+        });
+        self.set_world_pixels(pixels, true, false)
+    }
+
+    /// Queues one event for every tile the pixels are in
+    pub(crate) fn set_world_pixels(
+        &mut self,
+        world_pixels: impl IntoIterator<Item = (WorldPixelPos, Color, MaterialId)>,
+        create: bool,
+        delete_empty: bool,
+    ) {
+        let mut by_tile: HashMap<TilePos, Vec<(PixelPos, Color, MaterialId)>> = HashMap::new();
+        for (world_pixel, color, material) in world_pixels {
+            let (loc, pixel) = world_pixel.split(self.dimensions);
+            by_tile
+                .entry(loc)
+                .or_default()
+                .push((pixel, color, material));
+        }
+
+        for (loc, pixels) in by_tile {
+            if create {
+                self.require_chunk(loc);
+            } else if !self.has_chunk(loc) {
+                continue;
+            }
+
+            self.push_task(TileEvent::SetPixels {
+                loc,
+                pixels,
+                create,
+                delete_empty,
+            });
+        }
+    }
+
+    /// Gets the color of a pixel in world pixel coordinates
+    pub fn get_world_pixel(
+        &self,
+        world_pixel: WorldPixelPos,
+        chunks: &Query<&Chunk>,
+        tiles: &Query<&Tile>,
+    ) -> Option<Color> {
+        let (loc, pixel) = world_pixel.split(self.dimensions);
+        self.get_pixel(loc, pixel, chunks, tiles)
+    }
+
+    /// Gets the material of a pixel in world pixel coordinates
+    pub fn get_world_material(
+        &self,
+        world_pixel: WorldPixelPos,
+        chunks: &Query<&Chunk>,
+        tiles: &Query<&Tile>,
+    ) -> Option<MaterialId> {
+        let (loc, pixel) = world_pixel.split(self.dimensions);
+        let tile = self.get_tile(loc, chunks)?;
+        tiles.get(tile).ok()?.get_material(pixel)
+    }
+
+    pub fn delete_tile(&mut self, loc: TilePos) {
+        if !self.has_chunk(loc) && !self.pending.contains_key(&loc) {
             return;
         }
 
-        self.tasks
-            .push_back(TileEvent::DeleteTile { loc, mark: true });
+        self.push_task(TileEvent::DeleteTile { loc, mark: true });
+        self.pending.insert(loc, None);
 
         self.require_chunk(loc)
     }
 
-    pub fn delete_without_marker(&mut self, loc: IVec2) {
-        if !self.has_chunk(loc) {
+    pub fn delete_without_marker(&mut self, loc: TilePos) {
+        if !self.has_chunk(loc) && !self.pending.contains_key(&loc) {
             return;
         }
 
-        self.tasks
-            .push_back(TileEvent::DeleteTile { loc, mark: false });
+        self.push_task(TileEvent::DeleteTile { loc, mark: false });
+        self.pending.insert(loc, None);
 
         self.require_chunk(loc)
     }
 
-    pub fn get_tile(&mut self, loc: IVec2, chunks: &Query<&Chunk>) -> Option<Entity> {
+    /// Gets the tile at the location, including sets and deletes that are still queued
+    pub fn get_tile(&self, loc: TilePos, chunks: &Query<&Chunk>) -> Option<Entity> {
+        if let Some(pending) = self.pending.get(&loc) {
+            return *pending;
+        }
+
         if !self.has_chunk(loc) {
             return None;
         }
 
         if let Some(entity) = self.get_chunk(loc) {
             if let Ok(chunk) = chunks.get(entity) {
-                return chunk.get_tile(tile_from_location(loc));
+                return chunk.get_tile(loc.local(self.dimensions));
             }
         }
 
         None
     }
 
-    pub fn get_chunk(&self, loc: IVec2) -> Option<Entity> {
-        let loc = chunk_from_location(loc);
+    /// Gets the color of a pixel of the tile at the location
+    pub fn get_pixel(
+        &self,
+        loc: TilePos,
+        pixel: PixelPos,
+        chunks: &Query<&Chunk>,
+        tiles: &Query<&Tile>,
+    ) -> Option<Color> {
+        let tile = self.get_tile(loc, chunks)?;
+        tiles.get(tile).ok()?.get_pixel(pixel)
+    }
+
+    /// Finds the first pixel along the ray, see [`raycast`](crate::raycast)
+    pub fn raycast(
+        &self,
+        ray: PixelRay,
+        materials: &MaterialRegistry,
+        chunks: &Query<&Chunk>,
+        tiles: &Query<&Tile>,
+    ) -> Option<PixelRayHit> {
+        raycast(self, ray, materials, chunks, tiles)
+    }
+
+    /// Every solid pixel of the shape, see [`overlap`](crate::overlap)
+    pub fn overlap(
+        &self,
+        shape: &PixelShape,
+        alpha_threshold: f32,
+        materials: &MaterialRegistry,
+        chunks: &Query<&Chunk>,
+        tiles: &Query<&Tile>,
+    ) -> Vec<PixelOverlap> {
+        overlap(self, shape, alpha_threshold, materials, chunks, tiles)
+    }
+
+    /// Whether any pixel of the shape is solid
+    pub fn overlaps(
+        &self,
+        shape: &PixelShape,
+        alpha_threshold: f32,
+        materials: &MaterialRegistry,
+        chunks: &Query<&Chunk>,
+        tiles: &Query<&Tile>,
+    ) -> bool {
+        overlaps(self, shape, alpha_threshold, materials, chunks, tiles)
+    }
+
+    /// Moves the shape along `motion` until it would overlap a pixel, see [`sweep`](crate::overlap::sweep)
+    pub fn sweep(
+        &self,
+        shape: &PixelShape,
+        motion: IVec2,
+        alpha_threshold: f32,
+        materials: &MaterialRegistry,
+        chunks: &Query<&Chunk>,
+        tiles: &Query<&Tile>,
+    ) -> Option<PixelSweepHit> {
+        sweep(
+            self,
+            shape,
+            motion,
+            alpha_threshold,
+            materials,
+            chunks,
+            tiles,
+        )
+    }
+
+    /// Iterates over the location and entity of every chunk
+    pub fn chunks(&self) -> impl Iterator<Item = (ChunkPos, Entity)> + '_ {
+        self.chunks.iter().map(|(loc, entity)| (*loc, *entity))
+    }
+
+    /// Gets the chunk at a chunk location, rather than at a tile location
+    pub fn chunk_at(&self, loc: ChunkPos) -> Option<Entity> {
         self.chunks.get(&loc).copied()
     }
 
-    pub fn has_chunk(&self, loc: IVec2) -> bool {
-        let loc = chunk_from_location(loc);
-        self.chunks.contains_key(&loc)
+    /// Registers a chunk spawned outside of the event queue, the location is in chunks
+    pub(crate) fn insert_chunk(&mut self, loc: ChunkPos, entity: Entity) {
+        self.chunks.insert(loc, entity);
+    }
+
+    /// Whether any queued event affects the chunk
+    pub fn has_queued_edits_in_chunk(&self, loc: ChunkPos) -> bool {
+        self.queued_chunks.contains(&loc)
+    }
+
+    fn push_task(&mut self, task: TileEvent) {
+        self.queued_chunks.insert(task.chunk(self.dimensions));
+        self.tasks.push_back(task);
+    }
+
+    /// Collects the chunks affected by the queue again, after it was processed
+    fn collect_queued_chunks(&mut self) {
+        let dimensions = self.dimensions;
+        self.queued_chunks = self
+            .tasks
+            .iter()
+            .map(|task| task.chunk(dimensions))
+            .collect();
+    }
+
+    /// Forgets a chunk without despawning it
+    pub(crate) fn remove_chunk(&mut self, loc: ChunkPos) -> Option<Entity> {
+        self.chunks.remove(&loc)
+    }
+
+    /// Gets the chunk the tile is in
+    pub fn get_chunk(&self, loc: TilePos) -> Option<Entity> {
+        self.chunks.get(&loc.chunk(self.dimensions)).copied()
+    }
+
+    pub fn has_chunk(&self, loc: TilePos) -> bool {
+        self.chunks.contains_key(&loc.chunk(self.dimensions))
     }
 }
 
+/// Projects a world coordinate onto the tilemap's plane, in the tilemap's local space
+fn global_to_local(transform: &GlobalTransform, loc: Vec2) -> Vec2 {
+    transform
+        .affine()
+        .inverse()
+        .transform_point3(loc.extend(transform.translation().z))
+        .truncate()
+}
+
 pub fn tilemap_event_system(
     mut commands: Commands,
     mut tilemaps: Query<(Entity, &mut Tilemap)>,
     mut chunks: Query<(Entity, &mut Chunk)>,
     mut tiles: Query<(Entity, &mut Tile)>,
+    destructible: Query<(), With<Destructible>>,
     mut images: ResMut<Assets<Image>>,
+    mut events: TilemapEventWriters,
 ) {
     for (tilemap_entity, mut tilemap) in &mut tilemaps {
+        let dimensions = tilemap.dimensions;
         let mut remaining_tasks = VecDeque::new();
+        let mut created_tiles = HashMap::new();
         while let Some(event) = tilemap.tasks.pop_front() {
             match event {
-                TileEvent::MakeChunk(loc) => {
-                    if !tilemap.has_chunk(loc) {
+                TileEvent::MakeChunk(chunk_loc) => {
+                    if tilemap.chunk_at(chunk_loc).is_none() {
                         let entity = commands
                             .spawn(ChunkBundle::new(
-                                chunk_from_location(loc),
-                                Chunk::new(&mut images),
+                                chunk_loc,
+                                Chunk::with_dimensions(&mut images, dimensions),
                             ))
                             .id();
 
                         commands.entity(entity).set_parent(tilemap_entity);
 
-                        tilemap.chunks.insert(chunk_from_location(loc), entity);
+                        tilemap.chunks.insert(chunk_loc, entity);
+
+                        events.chunk_created.send(ChunkCreatedEvent {
+                            tilemap: tilemap_entity,
+                            loc: chunk_loc,
+                            chunk: entity,
+                        });
                     }
                 }
                 TileEvent::SetTile { loc, entity } => {
-                    let chunk_loc = chunk_from_location(loc);
+                    let chunk_loc = loc.chunk(dimensions);
 
                     if !tilemap.has_chunk(loc) {
-                        remaining_tasks.push_front(TileEvent::MakeChunk(loc));
+                        remaining_tasks.push_front(TileEvent::MakeChunk(chunk_loc));
                         remaining_tasks.push_back(TileEvent::SetTile { loc, entity });
                     } else if let Ok((chunk_entity, mut chunk)) =
                         chunks.get_mut(*tilemap.chunks.get(&chunk_loc).expect("chunk should exist"))
                     {
+                        let tile_loc = loc.local(dimensions);
+
+                        if let Some(previous) = chunk.get_tile(tile_loc) {
+                            events.tile_deleted.send(TileDeletedEvent {
+                                tilemap: tilemap_entity,
+                                loc,
+                                chunk: chunk_entity,
+                                tile: previous,
+                            });
+                        }
+
                         commands.entity(entity).set_parent(chunk_entity);
-                        chunk.set_tile_entity(tile_from_location(loc), entity, &mut commands)
+                        chunk.set_tile_entity(tile_loc, entity, &mut commands);
+
+                        if tilemap.pending.get(&loc) == Some(&Some(entity)) {
+                            tilemap.pending.remove(&loc);
+                        }
+
+                        events.tile_set.send(TileSetEvent {
+                            tilemap: tilemap_entity,
+                            loc,
+                            chunk: chunk_entity,
+                            tile: entity,
+                        });
                     } else {
                         remaining_tasks.push_back(TileEvent::SetTile { loc, entity })
                     }
                 }
                 TileEvent::DeleteTile { loc, mark } => {
-                    let chunk_loc = chunk_from_location(loc);
+                    let chunk_loc = loc.chunk(dimensions);
 
                     if tilemap.has_chunk(loc) {
-                        if let Ok((_, mut chunk)) = chunks.get_mut(
+                        if let Ok((chunk_entity, mut chunk)) = chunks.get_mut(
                             *tilemap
                                 .chunks
                                 .get_mut(&chunk_loc)
                                 .expect("Chunk should exist"),
                         ) {
+                            let tile_loc = loc.local(dimensions);
+
+                            if let Some(tile) = chunk.get_tile(tile_loc) {
+                                events.tile_deleted.send(TileDeletedEvent {
+                                    tilemap: tilemap_entity,
+                                    loc,
+                                    chunk: chunk_entity,
+                                    tile,
+                                });
+                            }
+
                             if mark {
-                                chunk.delete_tile(tile_from_location(loc), &mut commands);
+                                chunk.delete_tile(tile_loc, &mut commands);
                             } else {
-                                chunk.delete_unmarked(tile_from_location(loc), &mut commands);
+                                chunk.delete_unmarked(tile_loc, &mut commands);
+                            }
+
+                            if tilemap.pending.get(&loc) == Some(&None) {
+                                tilemap.pending.remove(&loc);
                             }
+                        } else {
+                            // The chunk was created this frame, wait for it to exist
+                            remaining_tasks.push_back(TileEvent::DeleteTile { loc, mark });
                         }
                     }
                 }
-                TileEvent::SetPixel { loc, pixel, color } => {
-                    let chunk_loc = chunk_from_location(loc);
+                TileEvent::SetPixels {
+                    loc,
+                    pixels,
+                    create,
+                    delete_empty,
+                } => {
+                    let chunk_loc = loc.chunk(dimensions);
 
                     if tilemap.has_chunk(loc) {
-                        if let Ok((_, mut chunk)) = chunks.get_mut(
+                        if let Ok((chunk_entity, mut chunk)) = chunks.get_mut(
                             *tilemap
                                 .chunks
                                 .get_mut(&chunk_loc)
                                 .expect("Chunk should exist"),
                         ) {
-                            if let Some(tile) = chunk.get_tile(tile_from_location(loc)) {
-                                tiles
-                                    .get_mut(tile)
-                                    .expect("Tile should exist")
-                                    .1
-                                    .set_pixel(pixel, color);
+                            let tile_loc = loc.local(dimensions);
+
+                            let tile = match chunk.get_tile(tile_loc) {
+                                Some(tile) => Some(tile),
+                                None if create => {
+                                    let tile = commands.spawn_empty().id();
+                                    commands.entity(tile).set_parent(chunk_entity);
+                                    chunk.set_tile_entity(tile_loc, tile, &mut commands);
+                                    created_tiles.insert(
+                                        tile,
+                                        (
+                                            loc,
+                                            Tile::from_color(Color::NONE, dimensions.tile_size()),
+                                        ),
+                                    );
+
+                                    events.tile_set.send(TileSetEvent {
+                                        tilemap: tilemap_entity,
+                                        loc,
+                                        chunk: chunk_entity,
+                                        tile,
+                                    });
+                                    Some(tile)
+                                }
+                                None => None,
+                            };
+
+                            if let Some(tile) = tile {
+                                // Tiles created this frame aren't spawned yet
+                                let pixel_count = match created_tiles.get_mut(&tile) {
+                                    Some((_, created)) => {
+                                        for (pixel, color, material) in &pixels {
+                                            created.set_material(*pixel, *material, *color);
+                                        }
+                                        created.pixel_count()
+                                    }
+                                    None => {
+                                        let mut tile =
+                                            tiles.get_mut(tile).expect("Tile should exist").1;
+                                        for (pixel, color, material) in &pixels {
+                                            tile.set_material(*pixel, *material, *color);
+                                        }
+                                        tile.pixel_count()
+                                    }
+                                };
+
+                                for (pixel, color, _) in &pixels {
+                                    events.pixel_changed.send(PixelChangedEvent {
+                                        tilemap: tilemap_entity,
+                                        loc,
+                                        pixel: *pixel,
+                                        color: *color,
+                                        chunk: chunk_entity,
+                                        tile,
+                                    });
+                                    chunk.update_pixel(tile_loc, *pixel);
+                                }
+
+                                // Destructible tiles are left to `tile_destruction`, which sends `TileDestroyedEvent`
+                                if delete_empty && pixel_count == 0 && !destructible.contains(tile)
+                                {
+                                    events.tile_deleted.send(TileDeletedEvent {
+                                        tilemap: tilemap_entity,
+                                        loc,
+                                        chunk: chunk_entity,
+                                        tile,
+                                    });
+                                    chunk.delete_tile(tile_loc, &mut commands);
+                                }
                             }
-                            chunk.update_tile(tile_from_location(loc));
+                        } else {
+                            // The chunk was created this frame, wait for it to exist
+                            remaining_tasks.push_back(TileEvent::SetPixels {
+                                loc,
+                                pixels,
+                                create,
+                                delete_empty,
+                            });
                         }
+                    } else if create {
+                        remaining_tasks.push_front(TileEvent::MakeChunk(chunk_loc));
+                        remaining_tasks.push_back(TileEvent::SetPixels {
+                            loc,
+                            pixels,
+                            create,
+                            delete_empty,
+                        });
                     }
                 }
             }
         }
         tilemap.tasks.append(&mut remaining_tasks);
+        tilemap.collect_queued_chunks();
+
+        for (entity, (loc, tile)) in created_tiles {
+            commands.entity(entity).insert(TileBundle::new(tile, loc));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::plugin::PixelPlugin;
+
+    fn app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .add_plugins(PixelPlugin);
+        let tilemap = app.world.spawn(TilemapBundle::default()).id();
+
+        (app, tilemap)
+    }
+
+    #[test]
+    fn queued_edits_are_visible() {
+        let (mut app, tilemap_entity) = app();
+        let loc = TilePos::new(3, -20);
+
+        let placed = app.world.run_system_once(
+            move |mut commands: Commands,
+                  mut tilemaps: Query<&mut Tilemap>,
+                  chunks: Query<&Chunk>| {
+                let mut tilemap = tilemaps.get_mut(tilemap_entity).unwrap();
+                let tile_size = tilemap.dimensions().tile_size();
+                let tile = || Tile::from_color(Color::RED, tile_size);
+
+                let first = tilemap.set_tile(&mut commands, loc, tile(), ()).unwrap();
+                assert_eq!(tilemap.get_tile(loc, &chunks), Some(first));
+                assert!(tilemap.has_queued_edits_in_chunk(loc.chunk(tilemap.dimensions())));
+                assert_eq!(
+                    tilemap.try_set_tile(&mut commands, &chunks, loc, tile(), ()),
+                    Err(SetTileError::Occupied(first))
+                );
+
+                tilemap.delete_tile(loc);
+                assert_eq!(tilemap.get_tile(loc, &chunks), None);
+                assert_eq!(
+                    tilemap.try_set_tile(
+                        &mut commands,
+                        &chunks,
+                        loc,
+                        Tile::from_color(Color::RED, tile_size + 1),
+                        ()
+                    ),
+                    Err(SetTileError::WrongSize {
+                        expected: tile_size,
+                        found: tile_size + 1
+                    })
+                );
+
+                tilemap
+                    .try_set_tile(&mut commands, &chunks, loc, tile(), ())
+                    .unwrap()
+            },
+        );
+
+        // The chunk is created in the first update, the tiles are placed in it in the second
+        app.update();
+        app.update();
+
+        let tilemap = app.world.get::<Tilemap>(tilemap_entity).unwrap();
+        assert!(tilemap.pending.is_empty());
+        assert!(!tilemap.has_queued_edits_in_chunk(loc.chunk(tilemap.dimensions())));
+        let chunk = app
+            .world
+            .get::<Chunk>(tilemap.get_chunk(loc).unwrap())
+            .unwrap();
+        assert_eq!(
+            chunk.get_tile(loc.local(tilemap.dimensions())),
+            Some(placed)
+        );
     }
 }
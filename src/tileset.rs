@@ -0,0 +1,283 @@
+//! Tile sheets sliced into named tiles.
+//!
+//! A tileset is loaded from a RON descriptor ending in `.tileset.ron`, which points to a PNG next to it:
+//!
+//! ```ron
+//! (
+//!     image: "lightslate.png",
+//!     names: {
+//!         "slate": 4,
+//!         "slate_top": 1,
+//!     },
+//!     animations: {
+//!         "shimmer": (row: 5, frame_duration: 0.25),
+//!     },
+//! )
+//! ```
+//!
+//! The sheet is cut into square cells of `tile_size` pixels, [`TILE_SIZE`] if it's left out.
+//! Cells are indexed row by row from the top left. Animations play the cells of a row from left to right.
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{
+        io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadDirectError, ParseAssetPathError,
+    },
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{animation::AnimatedTile, tile::Tile, TILE_SIZE};
+
+/// A sheet of tiles, handed out by name or index
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct Tileset {
+    tile_size: usize,
+    columns: usize,
+    rows: usize,
+    /// Row major from the top left
+    tiles: Vec<Tile>,
+    names: HashMap<String, usize>,
+    animations: HashMap<String, TilesetAnimation>,
+}
+
+impl Tileset {
+    /// Slices the image into cells of `tile_size` pixels, leftover pixels at the right and bottom are skipped
+    pub fn from_image(image: &Image, tile_size: usize) -> Self {
+        assert!(tile_size > 0, "Tile size must not be zero");
+
+        let columns = image.size().x as usize / tile_size;
+        let rows = image.size().y as usize / tile_size;
+
+        let mut tiles = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = column * tile_size;
+                let y = row * tile_size;
+                tiles.push(Tile::from_image(
+                    image,
+                    (x..x + tile_size, y..y + tile_size),
+                ));
+            }
+        }
+
+        Self {
+            tile_size,
+            columns,
+            rows,
+            tiles,
+            names: HashMap::new(),
+            animations: HashMap::new(),
+        }
+    }
+
+    /// Names the tile at `index`
+    pub fn with_name(mut self, name: impl Into<String>, index: usize) -> Self {
+        self.set_name(name, index);
+        self
+    }
+
+    /// Names the tile at `index`, replacing the tile the name was given to before
+    pub fn set_name(&mut self, name: impl Into<String>, index: usize) {
+        self.names.insert(name.into(), index);
+    }
+
+    /// Names an animation made of the cells of a row
+    pub fn set_animation(&mut self, name: impl Into<String>, animation: TilesetAnimation) {
+        self.animations.insert(name.into(), animation);
+    }
+
+    /// The tiles of a row, from left to right
+    pub fn row(&self, row: usize) -> Vec<Tile> {
+        if row >= self.rows {
+            return vec![];
+        }
+
+        self.tiles[row * self.columns..(row + 1) * self.columns].to_vec()
+    }
+
+    /// An animation playing the tiles of a row from left to right, `None` if the row doesn't exist
+    pub fn row_animation(&self, row: usize, frame_duration: f32) -> Option<AnimatedTile> {
+        AnimatedTile::from_tiles(self.row(row), frame_duration)
+    }
+
+    pub fn get_animation(&self, name: &str) -> Option<AnimatedTile> {
+        let animation = self.animations.get(name)?;
+        let mut tiles = self.row(animation.row);
+        if let Some(frames) = animation.frames {
+            tiles.truncate(frames);
+        }
+
+        AnimatedTile::from_tiles(tiles, animation.frame_duration)
+    }
+
+    /// The tile at `index`, counting row by row from the top left
+    pub fn get(&self, index: usize) -> Option<Tile> {
+        self.tiles.get(index).cloned()
+    }
+
+    /// The tile in `column` and `row`, from the top left
+    pub fn get_cell(&self, column: usize, row: usize) -> Option<Tile> {
+        if column >= self.columns {
+            return None;
+        }
+
+        self.get(row * self.columns + column)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<Tile> {
+        self.get(self.index_of(name)?)
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Every name and the index of the tile it refers to
+    pub fn names(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.names
+            .iter()
+            .map(|(name, index)| (name.as_str(), *index))
+    }
+
+    /// The width and height of a tile, in pixels
+    pub fn tile_size(&self) -> usize {
+        self.tile_size
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
+/// The contents of a `.tileset.ron` file
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TilesetDescriptor {
+    /// The path of the sheet, relative to the descriptor
+    pub image: String,
+    #[serde(default = "default_tile_size")]
+    pub tile_size: usize,
+    /// Names of tiles by index
+    #[serde(default)]
+    pub names: HashMap<String, usize>,
+    /// Animations by name
+    #[serde(default)]
+    pub animations: HashMap<String, TilesetAnimation>,
+}
+
+/// An animation made of the cells of a row of the sheet
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TilesetAnimation {
+    pub row: usize,
+    /// How many cells from the left of the row are frames, the whole row if it's left out
+    #[serde(default)]
+    pub frames: Option<usize>,
+    /// How long every frame is shown, in seconds
+    pub frame_duration: f32,
+}
+
+fn default_tile_size() -> usize {
+    TILE_SIZE
+}
+
+#[derive(Error, Debug)]
+pub enum TilesetLoaderError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Ron(#[from] ron::error::SpannedError),
+    #[error(transparent)]
+    InvalidPath(#[from] ParseAssetPathError),
+    #[error(transparent)]
+    LoadImage(#[from] LoadDirectError),
+    #[error("{0} is not an image")]
+    NotAnImage(String),
+    #[error("the tile size must not be zero")]
+    ZeroTileSize,
+    #[error("animation {name} uses row {row}, but the tileset only has {rows} rows")]
+    MissingRow {
+        name: String,
+        row: usize,
+        rows: usize,
+    },
+    #[error("tile {name} has index {index}, but the tileset only has {len} tiles")]
+    MissingTile {
+        name: String,
+        index: usize,
+        len: usize,
+    },
+}
+
+#[derive(Default)]
+pub struct TilesetLoader;
+
+impl AssetLoader for TilesetLoader {
+    type Asset = Tileset;
+    type Settings = ();
+    type Error = TilesetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Tileset, TilesetLoaderError>> {
+        Box::pin(async move {
+            let mut bytes = vec![];
+            reader.read_to_end(&mut bytes).await?;
+            let descriptor = ron::de::from_bytes::<TilesetDescriptor>(&bytes)?;
+            if descriptor.tile_size == 0 {
+                return Err(TilesetLoaderError::ZeroTileSize);
+            }
+
+            let image_path = load_context.asset_path().resolve_embed(&descriptor.image)?;
+            let image = load_context
+                .load_direct(image_path)
+                .await?
+                .take::<Image>()
+                .ok_or_else(|| TilesetLoaderError::NotAnImage(descriptor.image.clone()))?;
+
+            let mut tileset = Tileset::from_image(&image, descriptor.tile_size);
+            for (name, index) in descriptor.names {
+                if index >= tileset.len() {
+                    return Err(TilesetLoaderError::MissingTile {
+                        name,
+                        index,
+                        len: tileset.len(),
+                    });
+                }
+                tileset.set_name(name, index);
+            }
+            for (name, animation) in descriptor.animations {
+                if animation.row >= tileset.rows() {
+                    return Err(TilesetLoaderError::MissingRow {
+                        name,
+                        row: animation.row,
+                        rows: tileset.rows(),
+                    });
+                }
+                tileset.set_animation(name, animation);
+            }
+
+            Ok(tileset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tileset.ron"]
+    }
+}
@@ -1,58 +1,51 @@
-use std::cmp::Ordering;
-
-use bevy::prelude::{IVec2, Vec2};
-
-use crate::{CHUNK_SIZE, TILE_SIZE};
-
-pub fn align_loc_to_chunk(mut loc: i32) -> i32 {
-    match loc.cmp(&0) {
-        Ordering::Greater => loc / CHUNK_SIZE as i32,
-        Ordering::Less => {
-            let mut result = 0;
-            while loc < 0 {
-                loc += CHUNK_SIZE as i32;
-                result -= 1;
-            }
-            result
-        }
-        Ordering::Equal => 0,
-    }
+//! Conversions between the tilemap's local space and tile locations.
+//!
+//! Local space is the space of the tilemap entity's [`Transform`](bevy::prelude::Transform),
+//! one tile is one unit wide. Use the `global_*` methods on [`Tilemap`](crate::tilemap::Tilemap)
+//! for moved, scaled or rotated tilemaps.
+use bevy::prelude::Vec2;
+
+use crate::{
+    dimensions::TilemapDimensions,
+    position::{PixelPos, TilePos, WorldPixelPos},
+};
+
+/// The offset from the tilemap's origin to the bottom left corner of tile `(0, 0)`, in tiles.
+/// Chunk sprites are anchored at their center, so tile `(0, 0)` starts half a chunk down and left.
+pub fn chunk_anchor_offset(dimensions: TilemapDimensions) -> Vec2 {
+    Vec2::splat(dimensions.chunk_size() as f32 / 2.0)
 }
 
-pub fn chunk_from_location(loc: IVec2) -> IVec2 {
-    IVec2::new(align_loc_to_chunk(loc.x), align_loc_to_chunk(loc.y))
+/// Converts a local coordinate to the tile under it
+pub fn world_unit_to_tile(loc: Vec2, dimensions: TilemapDimensions) -> TilePos {
+    TilePos((loc + chunk_anchor_offset(dimensions)).floor().as_ivec2())
 }
 
-pub fn tile_from_location(loc: IVec2) -> IVec2 {
-    let loc_x = loc.x.rem_euclid(CHUNK_SIZE as i32) as usize;
-    let loc_y = loc.y.rem_euclid(CHUNK_SIZE as i32) as usize;
-
-    IVec2::new(loc_x as i32, loc_y as i32)
+/// Converts a local coordinate to the tile & pixel under it
+pub fn world_unit_to_pixel(loc: Vec2, dimensions: TilemapDimensions) -> (TilePos, PixelPos) {
+    world_unit_to_world_pixel(loc, dimensions).split(dimensions)
 }
 
-/// Converts a world coordinate to a tile location
-pub fn world_unit_to_tile(loc: Vec2) -> IVec2 {
-    IVec2::new(
-        (loc.x).ceil() as i32 + CHUNK_SIZE as i32 / 2 - 1,
-        (loc.y).ceil() as i32 + CHUNK_SIZE as i32 / 2 - 1,
+/// Converts a local coordinate to the world pixel under it
+pub fn world_unit_to_world_pixel(loc: Vec2, dimensions: TilemapDimensions) -> WorldPixelPos {
+    WorldPixelPos(
+        world_unit_to_pixel_space(loc, dimensions)
+            .floor()
+            .as_ivec2(),
     )
 }
 
-/// Converts a world coordinate to a tile & pixel location
-pub fn world_unit_to_pixel(loc: Vec2) -> (IVec2, IVec2) {
-    let world_loc = world_unit_to_tile(loc);
-
-    let mut leftover_loc = Vec2::new(
-        world_loc.x as f32 - loc.x - 7.0,
-        world_loc.y as f32 - loc.y - 7.0,
-    );
-    leftover_loc.x *= TILE_SIZE as f32;
-    leftover_loc.y *= TILE_SIZE as f32;
+/// Converts a local coordinate to world pixel space without rounding it to a pixel
+pub fn world_unit_to_pixel_space(loc: Vec2, dimensions: TilemapDimensions) -> Vec2 {
+    (loc + chunk_anchor_offset(dimensions)) * dimensions.tile_size() as f32
+}
 
-    let pixel = IVec2::new(
-        8 - (leftover_loc.x.ceil() as i32),
-        leftover_loc.y.ceil() as i32 - 1,
-    );
+/// The local coordinate of the center of a tile
+pub fn tile_to_world_unit(loc: TilePos, dimensions: TilemapDimensions) -> Vec2 {
+    loc.0.as_vec2() + 0.5 - chunk_anchor_offset(dimensions)
+}
 
-    (world_loc, pixel)
+/// The local coordinate of the center of a world pixel
+pub fn world_pixel_to_world_unit(loc: WorldPixelPos, dimensions: TilemapDimensions) -> Vec2 {
+    (loc.0.as_vec2() + 0.5) / dimensions.tile_size() as f32 - chunk_anchor_offset(dimensions)
 }